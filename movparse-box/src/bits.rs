@@ -0,0 +1,140 @@
+/// Reads big-endian bit fields from the most significant bit of `data`, as packed by
+/// `avcC`, `hvcC` or `sdtp`. Derived boxes read runs of `#[mp4(bits = N)]` fields with it.
+#[derive(Clone, Debug)]
+pub struct BitReader<'a> {
+    data: &'a [u8],
+    /// Position in bits
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    /// Next `bits` bits as the low bits of the result.
+    /// Returns `None` if `bits` exceeds 64 or fewer bits remain.
+    pub fn read(&mut self, bits: u32) -> Option<u64> {
+        if bits > 64 || bits as usize > self.remain() {
+            return None;
+        }
+        let mut value = 0u64;
+        for _ in 0..bits {
+            let bit = self.data[self.pos / 8] >> (7 - self.pos % 8) & 1;
+            value = value << 1 | bit as u64;
+            self.pos += 1;
+        }
+        Some(value)
+    }
+
+    /// Number of unread bits
+    pub fn remain(&self) -> usize {
+        self.data.len() * 8 - self.pos
+    }
+}
+
+/// Packs bit fields in the layout read by [`BitReader`]
+#[derive(Clone, Debug, Default)]
+pub struct BitWriter {
+    data: Vec<u8>,
+    /// Bits used in the last byte of `data`, 0 if it is full
+    used: u32,
+}
+
+impl BitWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends the low `bits` bits of `value`, at most 64
+    pub fn write(&mut self, value: u64, bits: u32) {
+        for idx in (0..bits.min(64)).rev() {
+            if self.used == 0 {
+                self.data.push(0);
+            }
+            let bit = (value >> idx & 1) as u8;
+            *self.data.last_mut().unwrap() |= bit << (7 - self.used);
+            self.used = (self.used + 1) % 8;
+        }
+    }
+
+    /// Written bytes, with the last one padded by zero bits
+    pub fn finish(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// Types of `#[mp4(bits = N)]` fields, holding up to `BITS` bits
+pub trait Bits: Sized {
+    const BITS: u32;
+    /// `bits` has no bits above `BITS` set
+    fn from_bits(bits: u64) -> Self;
+    fn to_bits(&self) -> u64;
+}
+
+macro_rules! impl_bits {
+    ($($ty:ty),*) => {
+        $(
+            impl Bits for $ty {
+                const BITS: u32 = <$ty>::BITS;
+
+                fn from_bits(bits: u64) -> Self {
+                    bits as $ty
+                }
+
+                fn to_bits(&self) -> u64 {
+                    *self as u64
+                }
+            }
+        )*
+    };
+}
+
+impl_bits!(u8, u16, u32, u64);
+
+impl Bits for bool {
+    const BITS: u32 = 1;
+
+    fn from_bits(bits: u64) -> Self {
+        bits != 0
+    }
+
+    fn to_bits(&self) -> u64 {
+        *self as u64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bits() {
+        // `avcC` reserved bits and lengthSizeMinusOne, then reserved bits and numOfSequenceParameterSets
+        let src = [0b1111_1111, 0b1110_0001];
+        let mut reader = BitReader::new(&src);
+        assert_eq!(reader.read(6), Some(0b11_1111));
+        assert_eq!(reader.read(2), Some(3));
+        assert_eq!(reader.read(3), Some(0b111));
+        assert_eq!(reader.remain(), 5);
+        assert_eq!(reader.read(6), None);
+        assert_eq!(reader.read(5), Some(1));
+
+        let mut writer = BitWriter::new();
+        writer.write(0b11_1111, 6);
+        writer.write(3, 2);
+        // bits above the width are dropped
+        writer.write(0xff, 3);
+        writer.write(1, 5);
+        assert_eq!(writer.finish(), src);
+
+        let mut writer = BitWriter::new();
+        writer.write(u64::MAX, 64);
+        writer.write(1, 1);
+        assert_eq!(
+            writer.finish(),
+            [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x80]
+        );
+        assert!(bool::from_bits(1) && !bool::from_bits(0));
+    }
+}
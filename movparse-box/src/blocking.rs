@@ -0,0 +1,721 @@
+//! Blocking counterparts of the reader and read traits.
+//!
+//! `#[derive(BoxRead)]` and `#[derive(RootRead)]` implement these traits alongside the async ones,
+//! so the same box definitions can be parsed without an async runtime.
+//! [`Reader`] reads from any [`Source`]: [`std::io::Read`] + [`std::io::Seek`] through [`Reader::new`],
+//! or an in-memory buffer through [`Reader::from_bytes`] / [`Reader::from_slice`], which never lock or seek.
+//! [`Reader::from_bytes`] hands out [`Bytes`] fields as slices of its buffer, and the types of [`crate::slice`]
+//! read from [`Reader::from_slice`] borrow `&'a [u8]` fields from the slice.
+//! ```rust
+//! use std::io::Cursor;
+//! use movparse_box::{blocking::{BoxRead, Reader}, BoxHeader, UnknownBox};
+//!
+//! let src = vec![0, 0, 0, 12, b'f', b'r', b'e', b'e', 0, 0, 0, 0];
+//! let limit = src.len() as u64;
+//! let mut reader = Reader::new(Cursor::new(src), limit);
+//! let header = BoxHeader::read_blocking(&mut reader).unwrap();
+//! let free = UnknownBox::read_body(header, &mut reader).unwrap();
+//! assert_eq!(&free.header.id, b"free");
+//! assert_eq!(reader.pos, 12);
+//! ```
+use std::{
+    io::{self, Cursor, Read, Seek, SeekFrom},
+    marker::PhantomData,
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+
+use byteorder::{ReadBytesExt, BE};
+use bytes::Bytes;
+
+use crate::{
+    visit::{check_body_fits, Visit, MIN_BOX_SIZE},
+    BoxHeader, BoxPath, Error, ErrorKind, RawBox, RawString, SingleBoxPlaceholder, U32Tag,
+    UnknownBox,
+};
+
+/// Random access byte source read by [`Reader`].
+/// Cloned alongside the reader, so cloning must be cheap and share the underlying data.
+pub trait Source: Clone {
+    fn read_exact_at(&self, pos: u64, buf: &mut [u8]) -> io::Result<()>;
+
+    /// Returns `len` bytes from `pos`, copied by default. [`Bytes`] overrides this to hand out the buffer itself.
+    fn read_bytes_at(&self, pos: u64, len: usize) -> io::Result<Bytes> {
+        let mut buf = vec![0; len];
+        self.read_exact_at(pos, &mut buf)?;
+        Ok(Bytes::from(buf))
+    }
+}
+
+/// [`Source`] over [`std::io::Read`] + [`std::io::Seek`], shared between cloned readers
+pub struct Shared<R>(pub Arc<Mutex<R>>);
+
+impl<R> Clone for Shared<R> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<R: Read + Seek> Source for Shared<R> {
+    fn read_exact_at(&self, pos: u64, buf: &mut [u8]) -> io::Result<()> {
+        // the guarded reader is always re-positioned before use, so a poisoned lock is still usable
+        let mut inner = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        inner.seek(SeekFrom::Start(pos))?;
+        inner.read_exact(buf)
+    }
+}
+
+fn slice_range(len: usize, pos: u64, size: usize) -> io::Result<Range<usize>> {
+    let start = usize::try_from(pos).unwrap_or(usize::MAX);
+    match start.checked_add(size) {
+        Some(end) if end <= len => Ok(start..end),
+        _ => Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!(
+                "{} bytes from {} is out of the buffer ({} bytes)",
+                size, pos, len
+            ),
+        )),
+    }
+}
+
+/// In-memory source. Reads never lock, and [`Source::read_bytes_at`] returns slices of the same buffer.
+impl Source for Bytes {
+    fn read_exact_at(&self, pos: u64, buf: &mut [u8]) -> io::Result<()> {
+        buf.copy_from_slice(&self[slice_range(self.len(), pos, buf.len())?]);
+        Ok(())
+    }
+
+    fn read_bytes_at(&self, pos: u64, len: usize) -> io::Result<Bytes> {
+        Ok(self.slice(slice_range(self.len(), pos, len)?))
+    }
+}
+
+/// In-memory source borrowing the buffer. Reads never lock. [`Bytes`] fields are copied out of it,
+/// `&'a [u8]` fields of the types of [`crate::slice`] borrow it.
+impl Source for &[u8] {
+    fn read_exact_at(&self, pos: u64, buf: &mut [u8]) -> io::Result<()> {
+        buf.copy_from_slice(&self[slice_range(self.len(), pos, buf.len())?]);
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct Reader<R: Source> {
+    pub source: R,
+    pub pos: u64,
+    pub limit: Option<u64>,
+    /// Box currently being read, used to locate errors
+    pub path: BoxPath,
+}
+
+impl<R: Read + Seek> Reader<Shared<R>> {
+    pub fn new(reader: R, limit: u64) -> Self {
+        Self::from_source(Shared(Arc::new(Mutex::new(reader))), limit)
+    }
+}
+
+impl Reader<Bytes> {
+    /// Reader over an in-memory buffer. Bytes read into [`Bytes`] share it without copying, as do the raw bytes of a
+    /// [`crate::RawString`], whose decoded text is still allocated. `Vec<u8>` fields are always copied.
+    pub fn from_bytes(bytes: impl Into<Bytes>) -> Self {
+        let bytes = bytes.into();
+        let limit = bytes.len() as u64;
+        Self::from_source(bytes, limit)
+    }
+}
+
+impl<'a> Reader<&'a [u8]> {
+    pub fn from_slice(slice: &'a [u8]) -> Self {
+        Self::from_source(slice, slice.len() as u64)
+    }
+
+    /// Reads `len` bytes as a slice of the buffer
+    pub fn read_slice(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        self.check_limit(len)?;
+        let source: &'a [u8] = self.source;
+        let range = slice_range(source.len(), self.pos, len).map_err(|e| self.error(e))?;
+        self.pos += len as u64;
+        Ok(&source[range])
+    }
+}
+
+impl<R: Source> Reader<R> {
+    pub fn from_source(source: R, limit: u64) -> Self {
+        Self {
+            source,
+            pos: 0,
+            limit: Some(limit),
+            path: BoxPath::default(),
+        }
+    }
+
+    /// Error located at the current box and position
+    pub fn error(&self, kind: impl Into<ErrorKind>) -> Error {
+        Error::new(kind.into(), self.path.clone(), self.pos)
+    }
+
+    fn check_limit(&self, size: usize) -> Result<(), Error> {
+        if let Some(limit) = self.limit {
+            #[cfg(feature = "tracing")]
+            {
+                tracing::trace!(
+                    "read {} bytes from {}. next position: {}, limit: {}",
+                    size,
+                    self.pos,
+                    self.pos.saturating_add(size as u64),
+                    limit
+                );
+            }
+            // sizes of boxes (e.g. a u64::MAX largesize) can overflow the position
+            if !matches!(self.pos.checked_add(size as u64), Some(end) if end <= limit) {
+                return Err(self.error(ErrorKind::Truncated {
+                    requested: size as u64,
+                    available: limit.saturating_sub(self.pos),
+                }));
+            }
+        } else {
+            #[cfg(feature = "tracing")]
+            {
+                tracing::trace!(
+                    "read {} bytes from {}. next position: {}",
+                    size,
+                    self.pos,
+                    self.pos.saturating_add(size as u64)
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_exact(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.check_limit(buf.len())?;
+        self.source
+            .read_exact_at(self.pos, buf)
+            .map_err(|e| self.error(e))?;
+        self.pos += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    /// Reads `len` bytes, without copying them when the source is [`Bytes`]
+    pub fn read_bytes(&mut self, len: usize) -> Result<Bytes, Error> {
+        self.check_limit(len)?;
+        let bytes = self
+            .source
+            .read_bytes_at(self.pos, len)
+            .map_err(|e| self.error(e))?;
+        self.pos += len as u64;
+        Ok(bytes)
+    }
+
+    pub fn set_limit(&mut self, limit: u64) {
+        self.limit = Some(limit + self.pos);
+    }
+
+    pub fn seek_from_current(&mut self, seek: i64) -> Result<(), Error> {
+        #[cfg(feature = "tracing")]
+        {
+            tracing::trace!("seek {} to {}", self.pos, self.pos as i64 + seek,);
+        }
+        self.pos = (self.pos as i64 + seek) as u64;
+        Ok(())
+    }
+
+    pub fn seek_from_start(&mut self, seek: u64) -> Result<(), Error> {
+        #[cfg(feature = "tracing")]
+        {
+            tracing::trace!("seek {} to {}", self.pos, seek,);
+        }
+        self.pos = seek;
+        Ok(())
+    }
+
+    pub fn clear_limit(&mut self) {
+        self.limit = None;
+    }
+
+    pub fn remain(&self) -> i64 {
+        self.limit
+            .map(|limit| limit as i64 - self.pos as i64)
+            .unwrap_or(i64::MAX)
+    }
+}
+
+impl BoxHeader {
+    pub fn read_blocking<R: Source>(reader: &mut Reader<R>) -> Result<BoxHeader, Error> {
+        let offset = reader.pos;
+        let mut id = [0u8; 4];
+        let mut size = [0u8; 4];
+
+        reader.read_exact(&mut size)?;
+        reader.read_exact(&mut id)?;
+
+        let size = ReadBytesExt::read_u32::<BE>(&mut Cursor::new(size)).unwrap();
+        let largesize = size == 1;
+        let size = if size == 0 {
+            // size == 0 is only allowed in toplevel section
+            reader.remain() as u64 + 8
+        } else if largesize {
+            let mut size = [0u8; 8];
+            reader.read_exact(&mut size)?;
+            ReadBytesExt::read_u64::<BE>(&mut Cursor::new(size)).unwrap()
+        } else {
+            size as u64
+        };
+        let usertype = if id == Self::UUID {
+            let mut usertype = [0u8; 16];
+            reader.read_exact(&mut usertype)?;
+            Some(usertype)
+        } else {
+            None
+        };
+        Self {
+            id,
+            size,
+            largesize,
+            usertype,
+        }
+        .validated()
+        .map_err(|kind| Error::new(kind, reader.path.clone(), offset))
+    }
+}
+
+pub trait AttrRead: Sized {
+    fn read_attr<R: Source>(reader: &mut Reader<R>) -> Result<Self, Error>;
+}
+
+pub trait BoxRead: Sized {
+    /// Box types accepted by `acceptable_tag`, `None` if not known at compile time
+    const TAGS: Option<&'static [[u8; 4]]> = None;
+    fn acceptable_tag(tag: [u8; 4]) -> bool;
+    /// Whether a box with `header` is read as `Self`. `uuid` boxes override this to check the extended type.
+    fn acceptable_header(header: &BoxHeader) -> bool {
+        Self::acceptable_tag(header.id)
+    }
+    fn read_body<R: Source>(header: BoxHeader, reader: &mut Reader<R>) -> Result<Self, Error>;
+}
+
+pub trait RootRead: Sized {
+    fn read<R: Source>(reader: &mut Reader<R>) -> Result<Self, Error>;
+}
+
+impl AttrRead for RawString {
+    fn read_attr<R: Source>(reader: &mut Reader<R>) -> Result<Self, Error> {
+        Ok(RawString::new(AttrRead::read_attr(reader)?))
+    }
+}
+
+impl AttrRead for Bytes {
+    fn read_attr<R: Source>(reader: &mut Reader<R>) -> Result<Self, Error> {
+        reader.read_bytes(reader.remain() as usize)
+    }
+}
+
+impl<T: BoxRead> AttrRead for T {
+    fn read_attr<R: Source>(reader: &mut Reader<R>) -> Result<Self, Error> {
+        let offset = reader.pos;
+        let header = BoxHeader::read_blocking(reader)?;
+        if Self::acceptable_header(&header) {
+            let parent = reader.path.clone();
+            reader.path = parent.join(header.id, None);
+            let result = BoxRead::read_body(header, reader);
+            reader.path = parent;
+            result
+        } else {
+            let tag = U32Tag { raw: header.id };
+            Err(Error::new(
+                ErrorKind::UnexpectedTag { tag },
+                reader.path.clone(),
+                offset,
+            ))
+        }
+    }
+}
+
+impl<T> AttrRead for PhantomData<T> {
+    fn read_attr<R: Source>(_: &mut Reader<R>) -> Result<Self, Error> {
+        Ok(PhantomData)
+    }
+}
+
+impl<const N: usize> AttrRead for [u8; N] {
+    fn read_attr<R: Source>(reader: &mut Reader<R>) -> Result<Self, Error> {
+        let mut buf = [0u8; N];
+        reader.read_exact(&mut buf[..])?;
+        Ok(buf)
+    }
+}
+
+impl AttrRead for u8 {
+    fn read_attr<R: Source>(reader: &mut Reader<R>) -> Result<Self, Error> {
+        let buf: [u8; 1] = AttrRead::read_attr(reader)?;
+        Ok(buf[0])
+    }
+}
+
+impl AttrRead for u16 {
+    fn read_attr<R: Source>(reader: &mut Reader<R>) -> Result<Self, Error> {
+        let buf: [u8; 2] = AttrRead::read_attr(reader)?;
+        Ok(u16::from_be_bytes(buf))
+    }
+}
+
+impl AttrRead for u32 {
+    fn read_attr<R: Source>(reader: &mut Reader<R>) -> Result<Self, Error> {
+        let buf: [u8; 4] = AttrRead::read_attr(reader)?;
+        Ok(u32::from_be_bytes(buf))
+    }
+}
+
+impl AttrRead for u64 {
+    fn read_attr<R: Source>(reader: &mut Reader<R>) -> Result<Self, Error> {
+        let buf: [u8; 8] = AttrRead::read_attr(reader)?;
+        Ok(u64::from_be_bytes(buf))
+    }
+}
+
+impl<T: AttrRead> AttrRead for Vec<T> {
+    fn read_attr<R: Source>(reader: &mut Reader<R>) -> Result<Self, Error> {
+        let mut buf = Vec::new();
+        loop {
+            match T::read_attr(reader) {
+                Ok(t) => {
+                    buf.push(t);
+                    if reader.remain() == 0 {
+                        return Ok(buf);
+                    }
+                }
+                Err(Error {
+                    kind: ErrorKind::Truncated { .. },
+                    ..
+                }) => {
+                    return Ok(buf);
+                }
+                Err(e) => {
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+impl AttrRead for U32Tag {
+    fn read_attr<R: Source>(reader: &mut Reader<R>) -> Result<Self, Error> {
+        let raw: [u8; 4] = AttrRead::read_attr(reader)?;
+        Ok(Self { raw })
+    }
+}
+
+pub trait BoxPlaceholder<T: BoxRead> {
+    type Output;
+    fn push(&mut self, value: T) -> Result<(), ErrorKind>;
+    fn get(self, name: &str) -> Result<Self::Output, ErrorKind>;
+    fn acceptable_tag(&self, tag: [u8; 4]) -> bool;
+    fn acceptable_header(&self, header: &BoxHeader) -> bool {
+        self.acceptable_tag(header.id)
+    }
+    /// Index of the next pushed box among its siblings, shown in error paths
+    fn next_index(&self) -> Option<usize> {
+        None
+    }
+    fn read_body<R: Source>(&self, header: BoxHeader, reader: &mut Reader<R>) -> Result<T, Error>;
+
+    /// Reads the body of a child box and pushes it, extending `reader.path` while it is parsed
+    fn read_child<R: Source>(
+        &mut self,
+        header: BoxHeader,
+        reader: &mut Reader<R>,
+    ) -> Result<(), Error> {
+        let parent = reader.path.clone();
+        reader.path = parent.join(header.id, self.next_index());
+        let result = self
+            .read_body(header, reader)
+            .and_then(|value| self.push(value).map_err(|kind| reader.error(kind)));
+        reader.path = parent;
+        result
+    }
+}
+
+pub trait BoxContainer<T: BoxRead, D: BoxPlaceholder<T>> {
+    fn placeholder() -> D;
+}
+
+impl<T: BoxRead> BoxPlaceholder<T> for SingleBoxPlaceholder<T> {
+    type Output = T;
+
+    fn acceptable_tag(&self, tag: [u8; 4]) -> bool {
+        T::acceptable_tag(tag)
+    }
+
+    fn acceptable_header(&self, header: &BoxHeader) -> bool {
+        T::acceptable_header(header)
+    }
+
+    fn read_body<R: Source>(&self, header: BoxHeader, reader: &mut Reader<R>) -> Result<T, Error> {
+        T::read_body(header, reader)
+    }
+
+    fn push(&mut self, value: T) -> Result<(), ErrorKind> {
+        self.push_single(value)
+    }
+
+    fn get(self, name: &str) -> Result<Self::Output, ErrorKind> {
+        self.get_single(name)
+    }
+}
+
+impl<T: BoxRead> BoxPlaceholder<T> for Option<T> {
+    type Output = Option<T>;
+
+    fn acceptable_tag(&self, tag: [u8; 4]) -> bool {
+        T::acceptable_tag(tag)
+    }
+
+    fn acceptable_header(&self, header: &BoxHeader) -> bool {
+        T::acceptable_header(header)
+    }
+
+    fn read_body<R: Source>(&self, header: BoxHeader, reader: &mut Reader<R>) -> Result<T, Error> {
+        T::read_body(header, reader)
+    }
+
+    fn push(&mut self, value: T) -> Result<(), ErrorKind> {
+        *self = Some(value);
+        Ok(())
+    }
+
+    fn get(self, _: &str) -> Result<Self::Output, ErrorKind> {
+        Ok(self)
+    }
+}
+
+impl<T: BoxRead> BoxPlaceholder<T> for Vec<T> {
+    type Output = Vec<T>;
+
+    fn acceptable_tag(&self, tag: [u8; 4]) -> bool {
+        T::acceptable_tag(tag)
+    }
+
+    fn acceptable_header(&self, header: &BoxHeader) -> bool {
+        T::acceptable_header(header)
+    }
+
+    fn read_body<R: Source>(&self, header: BoxHeader, reader: &mut Reader<R>) -> Result<T, Error> {
+        T::read_body(header, reader)
+    }
+
+    fn next_index(&self) -> Option<usize> {
+        Some(self.len())
+    }
+
+    fn push(&mut self, value: T) -> Result<(), ErrorKind> {
+        self.push(value);
+        Ok(())
+    }
+
+    fn get(self, _: &str) -> Result<Self::Output, ErrorKind> {
+        Ok(self)
+    }
+}
+
+impl<T: BoxRead> BoxContainer<T, SingleBoxPlaceholder<T>> for T {
+    fn placeholder() -> SingleBoxPlaceholder<T> {
+        SingleBoxPlaceholder { inner: None }
+    }
+}
+
+impl<T: BoxRead> BoxContainer<T, Option<T>> for Option<T> {
+    fn placeholder() -> Option<T> {
+        None
+    }
+}
+
+impl<T: BoxRead> BoxContainer<T, Vec<T>> for Vec<T> {
+    fn placeholder() -> Vec<T> {
+        Vec::new()
+    }
+}
+
+impl BoxRead for UnknownBox {
+    fn acceptable_tag(_: [u8; 4]) -> bool {
+        true
+    }
+
+    fn read_body<R: Source>(header: BoxHeader, reader: &mut Reader<R>) -> Result<Self, Error> {
+        reader.seek_from_current(header.body_size() as i64)?;
+        Ok(Self { header })
+    }
+}
+
+impl BoxRead for RawBox {
+    fn acceptable_tag(_: [u8; 4]) -> bool {
+        true
+    }
+
+    fn read_body<R: Source>(header: BoxHeader, reader: &mut Reader<R>) -> Result<Self, Error> {
+        let offset = reader.pos - header.header_size();
+        let data = reader.read_bytes(header.body_size())?;
+        Ok(Self {
+            header,
+            offset,
+            data,
+        })
+    }
+}
+
+/// Blocking counterpart of [`crate::visit::BoxVisitor`]
+pub trait BoxVisitor {
+    /// `path` includes the entered box, `offset` is the absolute offset of its header
+    fn enter_box(&mut self, path: &BoxPath, header: &BoxHeader, offset: u64) -> Visit;
+
+    /// Called for boxes entered with [`Visit::Body`]. `reader` is positioned at the body and limited to it;
+    /// whatever is left unread is skipped afterwards.
+    fn leaf_body<R: Source>(
+        &mut self,
+        _header: BoxHeader,
+        _reader: &mut Reader<R>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn exit_box(&mut self, _path: &BoxPath, _header: &BoxHeader) {}
+}
+
+/// Walks boxes until the limit of `reader`. Returns `false` if the visitor stopped the walk.
+pub fn walk<R: Source, V: BoxVisitor>(
+    reader: &mut Reader<R>,
+    visitor: &mut V,
+) -> Result<bool, Error> {
+    while reader.remain() >= MIN_BOX_SIZE {
+        let offset = reader.pos;
+        let header = BoxHeader::read_blocking(reader)?;
+        let parent = reader.path.clone();
+        reader.path = parent.join(header.id, None);
+        check_body_fits(&header, reader.remain()).map_err(|kind| reader.error(kind))?;
+        let mut reader2 = reader.clone();
+        reader2.set_limit(header.body_size() as u64);
+        match visitor.enter_box(&reader.path, &header, offset) {
+            Visit::Descend => {
+                if !walk(&mut reader2, visitor)? {
+                    return Ok(false);
+                }
+            }
+            Visit::Body => visitor.leaf_body(header, &mut reader2)?,
+            Visit::Skip => (),
+            Visit::Stop => return Ok(false),
+        }
+        visitor.exit_box(&reader.path, &header);
+        reader.seek_from_current(header.body_size() as i64)?;
+        reader.path = parent;
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_read_limit() {
+        let src = vec![0u8, 1, 2, 3, 4];
+        let limit = src.len() as u64;
+        let mut reader = Reader::new(Cursor::new(src), limit);
+        reader.set_limit(2);
+        assert!(<[u8; 3]>::read_attr(&mut reader).is_err());
+        assert_eq!(<[u8; 2]>::read_attr(&mut reader).unwrap(), [0, 1]);
+        reader.clear_limit();
+        assert_eq!(<[u8; 2]>::read_attr(&mut reader).unwrap(), [2, 3]);
+    }
+
+    #[test]
+    fn test_shared_reader() {
+        let src = vec![0u8, 1, 2, 3, 4];
+        let limit = src.len() as u64;
+        let mut reader = Reader::new(Cursor::new(src), limit);
+        assert_eq!(<[u8; 2]>::read_attr(&mut reader).unwrap(), [0, 1]);
+        let mut reader2 = reader.clone();
+        assert_eq!(<[u8; 2]>::read_attr(&mut reader).unwrap(), [2, 3]);
+        assert_eq!(<[u8; 3]>::read_attr(&mut reader2).unwrap(), [2, 3, 4]);
+    }
+
+    #[test]
+    fn test_vec() {
+        let src = vec![0u8, 1, 2, 3, 4];
+        let limit = src.len() as u64;
+        let mut reader = Reader::new(Cursor::new(src), limit);
+        reader.set_limit(4);
+        assert_eq!(
+            Vec::<[u8; 2]>::read_attr(&mut reader).unwrap(),
+            vec![[0, 1], [2, 3]]
+        );
+    }
+
+    #[test]
+    fn test_bytes_zero_copy() {
+        let src = Bytes::from(vec![
+            0u8, 0, 0, 13, b'n', b'a', b'm', b'e', b'h', b'e', b'l', b'l', b'o',
+        ]);
+        let mut reader = Reader::from_bytes(src.clone());
+        let header = BoxHeader::read_blocking(&mut reader).unwrap();
+        reader.set_limit(header.body_size() as u64);
+        let name = RawString::read_attr(&mut reader).unwrap();
+        assert_eq!(name.as_str(), "hello");
+        assert_eq!(name.as_bytes().as_ptr(), src[8..].as_ptr());
+        assert!(reader.read_bytes(1).is_err());
+    }
+
+    #[test]
+    fn test_slice() {
+        let src = [0u8, 1, 2, 3, 4];
+        let mut reader = Reader::from_slice(&src);
+        assert_eq!(u16::read_attr(&mut reader).unwrap(), 1);
+        reader.seek_from_current(1).unwrap();
+        assert_eq!(<[u8; 2]>::read_attr(&mut reader).unwrap(), [3, 4]);
+        reader.clear_limit();
+        assert!(matches!(
+            u8::read_attr(&mut reader).unwrap_err().kind,
+            ErrorKind::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof
+        ));
+    }
+
+    struct Mvhd {
+        entered: Vec<U32Tag>,
+        timescale: Option<u32>,
+    }
+
+    impl BoxVisitor for Mvhd {
+        fn enter_box(&mut self, _: &BoxPath, header: &BoxHeader, _: u64) -> Visit {
+            self.entered.push(U32Tag { raw: header.id });
+            match &header.id {
+                b"moov" => Visit::Descend,
+                b"mvhd" => Visit::Body,
+                _ => Visit::Skip,
+            }
+        }
+
+        fn leaf_body<R: Source>(
+            &mut self,
+            _: BoxHeader,
+            reader: &mut Reader<R>,
+        ) -> Result<(), Error> {
+            self.timescale = Some(u32::read_attr(reader)?);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_walk() {
+        let src = [
+            0u8, 0, 0, 28, b'm', b'o', b'o', b'v', //
+            0, 0, 0, 8, b'f', b'r', b'e', b'e', //
+            0, 0, 0, 12, b'm', b'v', b'h', b'd', 0, 0, 3, 232,
+        ];
+        let mut visitor = Mvhd {
+            entered: Vec::new(),
+            timescale: None,
+        };
+        assert!(walk(&mut Reader::from_slice(&src), &mut visitor).unwrap());
+        assert_eq!(visitor.timescale, Some(1000));
+        assert_eq!(visitor.entered.len(), 3);
+    }
+}
@@ -0,0 +1,41 @@
+use std::io;
+
+use tokio::io::AsyncWrite;
+
+use crate::AttrWrite;
+
+/// Field whose layout depends on a condition, such as the 64-bit version 1 and 32-bit version 0
+/// times of `mvhd`. Derived boxes read `Left` when the `#[mp4(cond)]`/`#[mp4(if_version)]` of the
+/// field holds and `Right` otherwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Either<L, R> {
+    Left(L),
+    Right(R),
+}
+
+impl<L: Copy + Into<u64>, R: Copy + Into<u64>> Either<L, R> {
+    pub fn to_u64(&self) -> u64 {
+        match self {
+            Self::Left(left) => (*left).into(),
+            Self::Right(right) => (*right).into(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<L: AttrWrite, R: AttrWrite> AttrWrite for Either<L, R> {
+    fn attr_size(&self) -> u64 {
+        match self {
+            Self::Left(left) => left.attr_size(),
+            Self::Right(right) => right.attr_size(),
+        }
+    }
+
+    async fn write_attr<W: AsyncWrite + Unpin + Send>(&self, writer: &mut W) -> io::Result<()> {
+        match self {
+            Self::Left(left) => left.write_attr(writer).await,
+            Self::Right(right) => right.write_attr(writer).await,
+        }
+    }
+}
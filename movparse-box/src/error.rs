@@ -0,0 +1,176 @@
+use std::{fmt, io, sync::Arc};
+
+use crate::U32Tag;
+
+/// Location of a box in the tree, e.g. `moov/trak[1]/mdia/minf/stbl/stsz`.
+/// Segments are shared between readers, so cloning and extending a path is cheap.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct BoxPath(Option<Arc<PathSegment>>);
+
+#[derive(PartialEq, Eq)]
+struct PathSegment {
+    parent: BoxPath,
+    id: [u8; 4],
+    index: Option<usize>,
+}
+
+impl BoxPath {
+    /// Path of the child box `id`. `index` is its position among siblings collected into the same `Vec`.
+    pub fn join(&self, id: [u8; 4], index: Option<usize>) -> Self {
+        Self(Some(Arc::new(PathSegment {
+            parent: self.clone(),
+            id,
+            index,
+        })))
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.0.is_none()
+    }
+
+    /// Segments from the outermost box to the innermost one
+    pub fn segments(&self) -> Vec<([u8; 4], Option<usize>)> {
+        let mut segments = Vec::new();
+        let mut cursor = &self.0;
+        while let Some(segment) = cursor {
+            segments.push((segment.id, segment.index));
+            cursor = &segment.parent.0;
+        }
+        segments.reverse();
+        segments
+    }
+}
+
+/// Writes a box type as text, escaping bytes that are not printable ASCII
+pub(crate) fn write_fourcc(f: &mut fmt::Formatter<'_>, id: [u8; 4]) -> fmt::Result {
+    for ch in id {
+        if ch.is_ascii_graphic() || ch == b' ' {
+            f.write_fmt(format_args!("{}", ch as char))?;
+        } else {
+            f.write_fmt(format_args!("\\x{:02X}", ch))?;
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for BoxPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_root() {
+            return f.write_str("(root)");
+        }
+        for (idx, (id, index)) in self.segments().into_iter().enumerate() {
+            if idx > 0 {
+                f.write_str("/")?;
+            }
+            write_fourcc(f, id)?;
+            if let Some(index) = index {
+                f.write_fmt(format_args!("[{}]", index))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for BoxPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!("\"{}\"", self))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ErrorKind {
+    #[error("required child box `{field}` is missing")]
+    MissingChild { field: String },
+    #[error("box appeared more than once")]
+    DuplicateChild,
+    #[error("unexpected box {tag:?}")]
+    UnexpectedTag { tag: U32Tag },
+    #[error("box {tag:?} declares {size} bytes, smaller than its {header_size} bytes header")]
+    SizeUnderflow {
+        tag: U32Tag,
+        size: u64,
+        header_size: u64,
+    },
+    #[error("`{field}` declares {count} entries but the box ends after {read}")]
+    CountMismatch {
+        field: String,
+        count: u64,
+        read: u64,
+    },
+    #[error(
+        "`{field}` declares {count} entries but {remaining} bytes remain in the box after them"
+    )]
+    TrailingBytes {
+        field: String,
+        count: u64,
+        remaining: u64,
+    },
+    #[error("`{field}` does not satisfy `{assertion}`")]
+    AssertionFailed { field: String, assertion: String },
+    #[error("`{field}` is {found}, expected {expected}")]
+    MagicMismatch {
+        field: String,
+        expected: String,
+        found: String,
+    },
+    /// Returned by `#[mp4(validate)]` hooks rejecting a box as a whole
+    #[error("invalid box: {reason}")]
+    Invalid { reason: String },
+    #[error("unknown value {value:#x}")]
+    UnknownValue { value: u64 },
+    #[error("unsupported version {version}")]
+    UnsupportedVersion { version: u8 },
+    #[error("no layout accepts a file with brands {brands:?}")]
+    UnknownFileType { brands: Vec<U32Tag> },
+    #[error("{requested} bytes requested but only {available} bytes remain in the box")]
+    Truncated { requested: u64, available: u64 },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Parse failure with the path and absolute offset where it happened
+#[derive(Debug, thiserror::Error)]
+#[error("{kind} (at {path}, offset {offset})")]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub path: BoxPath,
+    pub offset: u64,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, path: BoxPath, offset: u64) -> Self {
+        Self { kind, path, offset }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        match e.kind {
+            ErrorKind::Io(io) => io::Error::new(
+                io.kind(),
+                format!("{} (at {}, offset {})", io, e.path, e.offset),
+            ),
+            ErrorKind::Truncated { .. } => io::Error::new(io::ErrorKind::UnexpectedEof, e),
+            _ => io::Error::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_path_display() {
+        let path = BoxPath::default()
+            .join(*b"moov", None)
+            .join(*b"trak", Some(1))
+            .join(*b"mdia", None);
+        assert_eq!(path.to_string(), "moov/trak[1]/mdia");
+        assert_eq!(
+            BoxPath::default().join(*b"\xA9nam", None).to_string(),
+            "\\xA9nam"
+        );
+        assert_eq!(BoxPath::default().to_string(), "(root)");
+    }
+}
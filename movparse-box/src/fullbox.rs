@@ -0,0 +1,108 @@
+use std::{fmt, io};
+
+use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, AsyncWriteExt};
+
+use crate::{blocking, AttrRead, AttrWrite, Error, Reader};
+
+/// 24-bit `flags` of a full box
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FullBoxFlags(u32);
+
+impl FullBoxFlags {
+    pub const MAX: u32 = 0x00ff_ffff;
+
+    /// Returns `None` if `bits` does not fit in 24 bits
+    pub fn new(bits: u32) -> Option<Self> {
+        (bits <= Self::MAX).then_some(Self(bits))
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.0
+    }
+
+    /// Whether every bit of `mask` is set
+    pub fn contains(&self, mask: u32) -> bool {
+        self.0 & mask == mask
+    }
+
+    pub fn to_be_bytes(&self) -> [u8; 3] {
+        let [_, a, b, c] = self.0.to_be_bytes();
+        [a, b, c]
+    }
+
+    pub fn from_be_bytes([a, b, c]: [u8; 3]) -> Self {
+        Self(u32::from_be_bytes([0, a, b, c]))
+    }
+}
+
+impl fmt::Debug for FullBoxFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!("0x{:06x}", self.0))
+    }
+}
+
+/// `version` and `flags` opening the body of a full box (ISO/IEC 14496-12 `FullBox`)
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FullBoxHeader {
+    pub version: u8,
+    pub flags: FullBoxFlags,
+}
+
+impl FullBoxHeader {
+    fn from_be_bytes([version, a, b, c]: [u8; 4]) -> Self {
+        Self {
+            version,
+            flags: FullBoxFlags::from_be_bytes([a, b, c]),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl AttrRead for FullBoxHeader {
+    async fn read_attr<R: AsyncRead + AsyncSeek + Unpin + Send>(
+        reader: &mut Reader<R>,
+    ) -> Result<Self, Error> {
+        Ok(Self::from_be_bytes(AttrRead::read_attr(reader).await?))
+    }
+}
+
+impl blocking::AttrRead for FullBoxHeader {
+    fn read_attr<R: blocking::Source>(reader: &mut blocking::Reader<R>) -> Result<Self, Error> {
+        Ok(Self::from_be_bytes(blocking::AttrRead::read_attr(reader)?))
+    }
+}
+
+#[async_trait::async_trait]
+impl AttrWrite for FullBoxHeader {
+    fn attr_size(&self) -> u64 {
+        4
+    }
+
+    async fn write_attr<W: AsyncWrite + Unpin + Send>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_u8(self.version).await?;
+        writer.write_all(&self.flags.to_be_bytes()).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_full_box_header() {
+        let src = [1u8, 0, 0x01, 0x05];
+        let header: FullBoxHeader =
+            blocking::AttrRead::read_attr(&mut blocking::Reader::from_slice(&src)).unwrap();
+        assert_eq!(header.version, 1);
+        assert_eq!(header.flags.bits(), 0x105);
+        assert!(header.flags.contains(0x101));
+        assert!(!header.flags.contains(0x2));
+
+        let mut buf = Vec::new();
+        header.write_attr(&mut buf).await.unwrap();
+        assert_eq!(buf, src);
+        assert!(FullBoxFlags::new(0x0100_0000).is_none());
+    }
+}
@@ -0,0 +1,150 @@
+use std::{fmt::Debug, sync::OnceLock};
+
+use tokio::io::{AsyncRead, AsyncSeek};
+
+use crate::{blocking, BoxHeader, BoxPath, BoxRead, Error, Reader};
+
+/// Box whose body is parsed on first access.
+/// Reading it only records where the box is and skips the body, so expensive children such as
+/// sample tables cost nothing until [`Lazy::load`] is called. The parsed value is cached.
+pub struct Lazy<T> {
+    header: BoxHeader,
+    offset: u64,
+    path: BoxPath,
+    value: OnceLock<T>,
+}
+
+impl<T> Lazy<T> {
+    pub fn header(&self) -> BoxHeader {
+        self.header
+    }
+
+    /// Absolute offset of the body
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Value parsed by a previous load
+    pub fn get(&self) -> Option<&T> {
+        self.value.get()
+    }
+
+    pub fn into_inner(self) -> Option<T> {
+        self.value.into_inner()
+    }
+
+    fn cache(&self, value: T) -> &T {
+        // a concurrent load may have won the race, both parsed the same bytes
+        let _ = self.value.set(value);
+        self.value.get().unwrap()
+    }
+
+    fn recorded(header: BoxHeader, offset: u64, path: BoxPath) -> Self {
+        Self {
+            header,
+            offset,
+            path,
+            value: OnceLock::new(),
+        }
+    }
+}
+
+impl<T: BoxRead> Lazy<T> {
+    /// Parses the body with `reader`, which must read the same source this box was found in
+    pub async fn load<R: AsyncRead + AsyncSeek + Unpin + Send>(
+        &self,
+        reader: &Reader<R>,
+    ) -> Result<&T, Error> {
+        if let Some(value) = self.value.get() {
+            return Ok(value);
+        }
+        let mut reader = reader.clone();
+        reader.seek_from_start(self.offset).await?;
+        reader.set_limit(self.header.body_size() as u64);
+        reader.path = self.path.clone();
+        let value = T::read_body(self.header, &mut reader).await?;
+        Ok(self.cache(value))
+    }
+}
+
+impl<T: blocking::BoxRead> Lazy<T> {
+    /// Parses the body with `reader`, which must read the same source this box was found in
+    pub fn load_blocking<R: blocking::Source>(
+        &self,
+        reader: &blocking::Reader<R>,
+    ) -> Result<&T, Error> {
+        if let Some(value) = self.value.get() {
+            return Ok(value);
+        }
+        let mut reader = reader.clone();
+        reader.seek_from_start(self.offset)?;
+        reader.set_limit(self.header.body_size() as u64);
+        reader.path = self.path.clone();
+        let value = T::read_body(self.header, &mut reader)?;
+        Ok(self.cache(value))
+    }
+}
+
+impl<T: Clone> Clone for Lazy<T> {
+    fn clone(&self) -> Self {
+        Self {
+            header: self.header,
+            offset: self.offset,
+            path: self.path.clone(),
+            value: self.value.clone(),
+        }
+    }
+}
+
+impl<T: Debug> Debug for Lazy<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Lazy")
+            .field("header", &self.header)
+            .field("offset", &self.offset)
+            .field("value", &self.value.get())
+            .finish()
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: BoxRead> BoxRead for Lazy<T> {
+    const TAGS: Option<&'static [[u8; 4]]> = T::TAGS;
+
+    fn acceptable_tag(tag: [u8; 4]) -> bool {
+        T::acceptable_tag(tag)
+    }
+
+    fn acceptable_header(header: &BoxHeader) -> bool {
+        T::acceptable_header(header)
+    }
+
+    async fn read_body<R: AsyncRead + AsyncSeek + Unpin + Send>(
+        header: BoxHeader,
+        reader: &mut Reader<R>,
+    ) -> Result<Self, Error> {
+        let (offset, path) = (reader.pos, reader.path.clone());
+        reader.seek_from_current(header.body_size() as i64).await?;
+        Ok(Self::recorded(header, offset, path))
+    }
+}
+
+impl<T: blocking::BoxRead> blocking::BoxRead for Lazy<T> {
+    const TAGS: Option<&'static [[u8; 4]]> = T::TAGS;
+
+    fn acceptable_tag(tag: [u8; 4]) -> bool {
+        T::acceptable_tag(tag)
+    }
+
+    fn acceptable_header(header: &BoxHeader) -> bool {
+        T::acceptable_header(header)
+    }
+
+    fn read_body<R: blocking::Source>(
+        header: BoxHeader,
+        reader: &mut blocking::Reader<R>,
+    ) -> Result<Self, Error> {
+        let lazy = Self::recorded(header, reader.pos, reader.path.clone());
+        reader.seek_from_current(header.body_size() as i64)?;
+        Ok(lazy)
+    }
+}
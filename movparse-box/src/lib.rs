@@ -1,14 +1,40 @@
 use std::{
     fmt::Debug,
     io::{self, Cursor},
+    marker::PhantomData,
     sync::Arc,
 };
 
+mod bits;
+pub mod blocking;
+mod either;
+mod error;
+mod fullbox;
+mod lazy;
+pub mod slice;
+pub mod stream;
+mod top_level;
+pub mod tree;
 pub mod util;
+pub mod visit;
+mod write;
+
+pub use bits::{BitReader, BitWriter, Bits};
+pub use bytes::Bytes;
+pub use either::Either;
+pub use error::{BoxPath, Error, ErrorKind};
+pub use fullbox::{FullBoxFlags, FullBoxHeader};
+pub use lazy::Lazy;
+pub use top_level::TopLevel;
+pub use write::{AttrWrite, BoxWrite, ChildWrite};
+
+/// Used by parsers derived with the `tracing` feature to open a span per box
+#[cfg(feature = "tracing")]
+pub use tracing;
 
 use byteorder::{ReadBytesExt, BE};
 use tokio::{
-    io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, SeekFrom},
+    io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt, SeekFrom},
     sync::Mutex,
 };
 
@@ -18,6 +44,10 @@ use tokio::{
 pub struct BoxHeader {
     pub id: [u8; 4],
     pub size: u64,
+    /// `size` is encoded in the 64-bit `largesize` field
+    pub largesize: bool,
+    /// Extended type following the header of `uuid` boxes
+    pub usertype: Option<[u8; 16]>,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -29,10 +59,25 @@ pub struct U32Tag {
 
 #[derive(Clone, PartialEq, Eq)]
 pub struct RawString {
-    raw: Vec<u8>,
+    raw: Bytes,
     str: String,
 }
 
+impl RawString {
+    pub fn new(raw: Bytes) -> Self {
+        let str = String::from_utf8_lossy(&raw).to_string();
+        Self { raw, str }
+    }
+
+    pub fn as_bytes(&self) -> &Bytes {
+        &self.raw
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.str
+    }
+}
+
 #[cfg(feature = "serde")]
 impl serde::Serialize for RawString {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -60,7 +105,7 @@ impl<'de> serde::de::Visitor<'de> for RawStringVisitor {
     {
         Ok(RawString {
             str: v.to_owned(),
-            raw: v.as_bytes().to_vec(),
+            raw: Bytes::copy_from_slice(v.as_bytes()),
         })
     }
 }
@@ -92,9 +137,13 @@ impl std::fmt::Debug for U32Tag {
 }
 
 impl BoxHeader {
+    /// Box type of boxes identified by [`BoxHeader::usertype`]
+    pub const UUID: [u8; 4] = *b"uuid";
+
     pub async fn read<R: AsyncRead + AsyncSeek + Unpin + Send>(
         reader: &mut Reader<R>,
-    ) -> io::Result<BoxHeader> {
+    ) -> Result<BoxHeader, Error> {
+        let offset = reader.pos;
         let mut id = [0u8; 4];
         let mut size = [0u8; 4];
 
@@ -102,22 +151,43 @@ impl BoxHeader {
         reader.read_exact(&mut id).await?;
 
         let size = ReadBytesExt::read_u32::<BE>(&mut Cursor::new(size)).unwrap();
+        let largesize = size == 1;
         let size = if size == 0 {
-            // TODO: improve this code
             // size == 0 is only allowed in toplevel section
-            (reader.remain() - 4) as u64
-        } else if size == 1 {
+            reader.remain() as u64 + 8
+        } else if largesize {
             let mut size = [0u8; 8];
             reader.read_exact(&mut size).await?;
             ReadBytesExt::read_u64::<BE>(&mut Cursor::new(size)).unwrap()
         } else {
             size as u64
         };
-        if size < 8 {
-            return Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("{:?}: size must be larger than 8", U32Tag { raw: id }),
-            ));
+        let usertype = if id == Self::UUID {
+            let mut usertype = [0u8; 16];
+            reader.read_exact(&mut usertype).await?;
+            Some(usertype)
+        } else {
+            None
+        };
+        Self {
+            id,
+            size,
+            largesize,
+            usertype,
+        }
+        .validated()
+        .map_err(|kind| Error::new(kind, reader.path.clone(), offset))
+    }
+
+    fn validated(self) -> Result<Self, ErrorKind> {
+        let id = self.id;
+        let size = self.size;
+        if size < self.header_size() {
+            return Err(ErrorKind::SizeUnderflow {
+                tag: U32Tag { raw: id },
+                size,
+                header_size: self.header_size(),
+            });
         }
         #[cfg(feature = "tracing")]
         {
@@ -132,11 +202,64 @@ impl BoxHeader {
                 size
             );
         }
-        Ok(Self { id, size })
+        Ok(self)
+    }
+
+    pub async fn write<W: AsyncWrite + Unpin + Send>(&self, writer: &mut W) -> io::Result<()> {
+        if self.largesize {
+            writer.write_u32(1).await?;
+            writer.write_all(&self.id).await?;
+            writer.write_u64(self.size).await?;
+        } else {
+            writer.write_u32(self.size as u32).await?;
+            writer.write_all(&self.id).await?;
+        }
+        if let Some(usertype) = &self.usertype {
+            writer.write_all(usertype).await?;
+        }
+        Ok(())
+    }
+
+    /// Returns a copy of this header sized to wrap `body_size` bytes.
+    /// `largesize` is kept if it was already used and turned on when the box no longer fits in 32 bits.
+    pub fn resized(&self, body_size: u64) -> Self {
+        let compact = Self {
+            largesize: false,
+            ..*self
+        };
+        let largesize = self.largesize || compact.header_size() + body_size > u32::MAX as u64;
+        let header = Self { largesize, ..*self };
+        Self {
+            size: header.header_size() + body_size,
+            ..header
+        }
+    }
+
+    pub fn header_size(&self) -> u64 {
+        let size = if self.largesize { 16 } else { 8 };
+        if self.usertype.is_some() {
+            size + 16
+        } else {
+            size
+        }
     }
 
     pub fn body_size(&self) -> usize {
-        (self.size - 8) as usize
+        (self.size - self.header_size()) as usize
+    }
+}
+
+struct Uuid([u8; 16]);
+
+impl Debug for Uuid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (idx, byte) in self.0.iter().enumerate() {
+            if matches!(idx, 4 | 6 | 8 | 10) {
+                f.write_str("-")?;
+            }
+            f.write_fmt(format_args!("{:02x}", byte))?;
+        }
+        Ok(())
     }
 }
 
@@ -145,6 +268,8 @@ impl Debug for BoxHeader {
         f.debug_struct("BoxHeader")
             .field("id", &U32Tag { raw: self.id })
             .field("size", &self.size)
+            .field("largesize", &self.largesize)
+            .field("usertype", &self.usertype.map(Uuid))
             .finish()
     }
 }
@@ -153,6 +278,8 @@ pub struct Reader<R: AsyncRead + AsyncSeek + Unpin + Send> {
     pub inner: Arc<Mutex<R>>,
     pub pos: u64,
     pub limit: Option<u64>,
+    /// Box currently being read, used to locate errors
+    pub path: BoxPath,
 }
 
 impl<R: AsyncRead + AsyncSeek + Unpin + Send> Clone for Reader<R> {
@@ -161,6 +288,7 @@ impl<R: AsyncRead + AsyncSeek + Unpin + Send> Clone for Reader<R> {
             inner: self.inner.clone(),
             pos: self.pos,
             limit: self.limit,
+            path: self.path.clone(),
         }
     }
 }
@@ -171,14 +299,17 @@ impl<R: AsyncRead + Unpin + Send + AsyncSeek> Reader<R> {
             inner: Arc::new(Mutex::new(reader)),
             pos: 0,
             limit: Some(limit),
+            path: BoxPath::default(),
         }
     }
 
-    pub async fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let mut inner = self.inner.lock().await;
-        inner.seek(SeekFrom::Start(self.pos)).await?;
-        let size = inner.read_exact(buf).await?;
+    /// Error located at the current box and position
+    pub fn error(&self, kind: impl Into<ErrorKind>) -> Error {
+        Error::new(kind.into(), self.path.clone(), self.pos)
+    }
 
+    pub async fn read_exact(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let size = buf.len();
         if let Some(limit) = self.limit {
             #[cfg(feature = "tracing")]
             {
@@ -191,10 +322,10 @@ impl<R: AsyncRead + Unpin + Send + AsyncSeek> Reader<R> {
                 );
             }
             if self.pos + size as u64 > limit {
-                return Err(io::Error::new(
-                    io::ErrorKind::OutOfMemory,
-                    format!("pos: {} over the limit {}", self.pos + size as u64, limit),
-                ));
+                return Err(self.error(ErrorKind::Truncated {
+                    requested: size as u64,
+                    available: limit.saturating_sub(self.pos),
+                }));
             }
         } else {
             #[cfg(feature = "tracing")]
@@ -202,11 +333,17 @@ impl<R: AsyncRead + Unpin + Send + AsyncSeek> Reader<R> {
                 tracing::trace!(
                     "read {} bytes from {}. next position: {}",
                     size,
-                    self.pos - size as u64,
-                    self.pos
+                    self.pos,
+                    self.pos + size as u64
                 );
             }
         }
+        let mut inner = self.inner.lock().await;
+        let read = async {
+            inner.seek(SeekFrom::Start(self.pos)).await?;
+            inner.read_exact(buf).await
+        };
+        let size = read.await.map_err(|e| self.error(e))?;
         self.pos += size as u64;
         Ok(size)
     }
@@ -215,26 +352,22 @@ impl<R: AsyncRead + Unpin + Send + AsyncSeek> Reader<R> {
         self.limit = Some(limit + self.pos);
     }
 
-    pub async fn seek_from_current(&mut self, seek: i64) -> io::Result<()> {
+    /// Moves the position. The inner reader is repositioned on the next read, so skipping
+    /// never touches it and forward-only inputs only discard the bytes that are skipped over.
+    pub async fn seek_from_current(&mut self, seek: i64) -> Result<(), Error> {
         #[cfg(feature = "tracing")]
         {
             tracing::trace!("seek {} to {}", self.pos, self.pos as i64 + seek,);
         }
-        self.inner
-            .lock()
-            .await
-            .seek(SeekFrom::Current(seek as i64))
-            .await?;
         self.pos = (self.pos as i64 + seek) as u64;
         Ok(())
     }
 
-    pub async fn seek_from_start(&mut self, seek: u64) -> io::Result<()> {
+    pub async fn seek_from_start(&mut self, seek: u64) -> Result<(), Error> {
         #[cfg(feature = "tracing")]
         {
             tracing::trace!("seek {} to {}", self.pos, seek,);
         }
-        self.inner.lock().await.seek(SeekFrom::Start(seek)).await?;
         self.pos = seek;
         Ok(())
     }
@@ -254,19 +387,26 @@ impl<R: AsyncRead + Unpin + Send + AsyncSeek> Reader<R> {
 pub trait AttrRead: Sized {
     async fn read_attr<R: AsyncRead + AsyncSeek + Unpin + Send>(
         reader: &mut Reader<R>,
-    ) -> io::Result<Self>;
+    ) -> Result<Self, Error>;
 }
 
 #[async_trait::async_trait]
 impl AttrRead for RawString {
     async fn read_attr<R: AsyncRead + AsyncSeek + Unpin + Send>(
         reader: &mut Reader<R>,
-    ) -> io::Result<Self> {
-        let mut buf = Vec::new();
-        buf.resize(reader.remain() as usize, 0);
+    ) -> Result<Self, Error> {
+        Ok(RawString::new(AttrRead::read_attr(reader).await?))
+    }
+}
+
+#[async_trait::async_trait]
+impl AttrRead for Bytes {
+    async fn read_attr<R: AsyncRead + AsyncSeek + Unpin + Send>(
+        reader: &mut Reader<R>,
+    ) -> Result<Self, Error> {
+        let mut buf = vec![0; reader.remain() as usize];
         reader.read_exact(&mut buf).await?;
-        let str = String::from_utf8_lossy(&buf).to_string();
-        Ok(Self { raw: buf, str })
+        Ok(Bytes::from(buf))
     }
 }
 
@@ -274,15 +414,21 @@ impl AttrRead for RawString {
 impl<T: BoxRead> AttrRead for T {
     async fn read_attr<R: AsyncRead + AsyncSeek + Unpin + Send>(
         reader: &mut Reader<R>,
-    ) -> io::Result<Self> {
+    ) -> Result<Self, Error> {
+        let offset = reader.pos;
         let header = BoxHeader::read(reader).await?;
-        if Self::acceptable_tag(header.id) {
-            BoxRead::read_body(header, reader).await
+        if Self::acceptable_header(&header) {
+            let parent = reader.path.clone();
+            reader.path = parent.join(header.id, None);
+            let result = BoxRead::read_body(header, reader).await;
+            reader.path = parent;
+            result
         } else {
-            let u32tag = U32Tag { raw: header.id };
-            Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("{:?} is not acceptable for me", u32tag),
+            let tag = U32Tag { raw: header.id };
+            Err(Error::new(
+                ErrorKind::UnexpectedTag { tag },
+                reader.path.clone(),
+                offset,
             ))
         }
     }
@@ -290,25 +436,42 @@ impl<T: BoxRead> AttrRead for T {
 
 #[async_trait::async_trait]
 pub trait BoxRead: Sized {
+    /// Box types accepted by `acceptable_tag`, `None` if not known at compile time.
+    /// Derived boxes list theirs so that sibling fields accepting the same box type are rejected.
+    const TAGS: Option<&'static [[u8; 4]]> = None;
     fn acceptable_tag(tag: [u8; 4]) -> bool;
+    /// Whether a box with `header` is read as `Self`. `uuid` boxes override this to check the extended type.
+    fn acceptable_header(header: &BoxHeader) -> bool {
+        Self::acceptable_tag(header.id)
+    }
     async fn read_body<R: AsyncRead + AsyncSeek + Unpin + Send>(
         header: BoxHeader,
         reader: &mut Reader<R>,
-    ) -> io::Result<Self>;
+    ) -> Result<Self, Error>;
 }
 
 #[async_trait::async_trait]
 pub trait RootRead: Sized {
     async fn read<R: AsyncRead + AsyncSeek + Unpin + Send>(
         reader: &mut Reader<R>,
-    ) -> io::Result<Self>;
+    ) -> Result<Self, Error>;
+}
+
+/// Marker fields of generic box types, such as `PhantomData<&'a ()>`, occupy no bytes
+#[async_trait::async_trait]
+impl<T> AttrRead for PhantomData<T> {
+    async fn read_attr<R: AsyncRead + AsyncSeek + Unpin + Send>(
+        _: &mut Reader<R>,
+    ) -> Result<Self, Error> {
+        Ok(PhantomData)
+    }
 }
 
 #[async_trait::async_trait]
 impl<const N: usize> AttrRead for [u8; N] {
     async fn read_attr<R: AsyncRead + AsyncSeek + Unpin + Send>(
         reader: &mut Reader<R>,
-    ) -> io::Result<Self> {
+    ) -> Result<Self, Error> {
         let mut buf = [0u8; N];
         reader.read_exact(&mut buf[..]).await?;
         Ok(buf)
@@ -319,7 +482,7 @@ impl<const N: usize> AttrRead for [u8; N] {
 impl AttrRead for u8 {
     async fn read_attr<R: AsyncRead + AsyncSeek + Unpin + Send>(
         reader: &mut Reader<R>,
-    ) -> io::Result<Self> {
+    ) -> Result<Self, Error> {
         let mut buf = [0u8; 1];
         reader.read_exact(&mut buf[..]).await?;
         Ok(buf[0])
@@ -330,7 +493,7 @@ impl AttrRead for u8 {
 impl AttrRead for u16 {
     async fn read_attr<R: AsyncRead + AsyncSeek + Unpin + Send>(
         reader: &mut Reader<R>,
-    ) -> io::Result<Self> {
+    ) -> Result<Self, Error> {
         let mut buf = [0u8; 2];
         reader.read_exact(&mut buf[..]).await?;
         let mut buf = io::Cursor::new(buf);
@@ -342,7 +505,7 @@ impl AttrRead for u16 {
 impl AttrRead for u64 {
     async fn read_attr<R: AsyncRead + AsyncSeek + Unpin + Send>(
         reader: &mut Reader<R>,
-    ) -> io::Result<Self> {
+    ) -> Result<Self, Error> {
         let mut buf = [0u8; 8];
         reader.read_exact(&mut buf[..]).await?;
         let mut buf = io::Cursor::new(buf);
@@ -354,7 +517,7 @@ impl AttrRead for u64 {
 impl AttrRead for u32 {
     async fn read_attr<R: AsyncRead + AsyncSeek + Unpin + Send>(
         reader: &mut Reader<R>,
-    ) -> io::Result<Self> {
+    ) -> Result<Self, Error> {
         let mut buf = [0u8; 4];
         reader.read_exact(&mut buf[..]).await?;
         let mut buf = io::Cursor::new(buf);
@@ -366,7 +529,7 @@ impl AttrRead for u32 {
 impl<T: AttrRead + Send> AttrRead for Vec<T> {
     async fn read_attr<R: AsyncRead + AsyncSeek + Unpin + Send>(
         reader: &mut Reader<R>,
-    ) -> io::Result<Self> {
+    ) -> Result<Self, Error> {
         let mut buf = Vec::new();
         loop {
             match T::read_attr(reader).await {
@@ -376,7 +539,10 @@ impl<T: AttrRead + Send> AttrRead for Vec<T> {
                         return Ok(buf);
                     }
                 }
-                Err(e) if e.kind() == io::ErrorKind::OutOfMemory => {
+                Err(Error {
+                    kind: ErrorKind::Truncated { .. },
+                    ..
+                }) => {
                     return Ok(buf);
                 }
                 Err(e) => {
@@ -391,7 +557,7 @@ impl<T: AttrRead + Send> AttrRead for Vec<T> {
 impl AttrRead for U32Tag {
     async fn read_attr<R: AsyncRead + AsyncSeek + Unpin + Send>(
         reader: &mut Reader<R>,
-    ) -> io::Result<Self> {
+    ) -> Result<Self, Error> {
         let raw: [u8; 4] = AttrRead::read_attr(reader).await?;
         Ok(Self { raw })
     }
@@ -400,14 +566,40 @@ impl AttrRead for U32Tag {
 #[async_trait::async_trait]
 pub trait BoxPlaceholder<T: BoxRead> {
     type Output;
-    fn push(&mut self, value: T) -> io::Result<()>;
-    fn get(self, name: &str) -> io::Result<Self::Output>;
+    fn push(&mut self, value: T) -> Result<(), ErrorKind>;
+    fn get(self, name: &str) -> Result<Self::Output, ErrorKind>;
     fn acceptable_tag(&self, tag: [u8; 4]) -> bool;
+    fn acceptable_header(&self, header: &BoxHeader) -> bool {
+        self.acceptable_tag(header.id)
+    }
+    /// Index of the next pushed box among its siblings, shown in error paths
+    fn next_index(&self) -> Option<usize> {
+        None
+    }
     async fn read_body<R: AsyncRead + AsyncSeek + Unpin + Send>(
         &self,
         header: BoxHeader,
         reader: &mut Reader<R>,
-    ) -> io::Result<T>;
+    ) -> Result<T, Error>;
+
+    /// Reads the body of a child box and pushes it, extending `reader.path` while it is parsed
+    async fn read_child<R: AsyncRead + AsyncSeek + Unpin + Send>(
+        &mut self,
+        header: BoxHeader,
+        reader: &mut Reader<R>,
+    ) -> Result<(), Error>
+    where
+        Self: Send,
+    {
+        let parent = reader.path.clone();
+        reader.path = parent.join(header.id, self.next_index());
+        let result = match self.read_body(header, reader).await {
+            Ok(value) => self.push(value).map_err(|kind| reader.error(kind)),
+            Err(e) => Err(e),
+        };
+        reader.path = parent;
+        result
+    }
 }
 
 pub trait BoxContainer<T: BoxRead + Sync, D: BoxPlaceholder<T>> {
@@ -418,6 +610,25 @@ pub struct SingleBoxPlaceholder<T> {
     inner: Option<T>,
 }
 
+impl<T> SingleBoxPlaceholder<T> {
+    fn push_single(&mut self, value: T) -> Result<(), ErrorKind> {
+        if self.inner.is_some() {
+            return Err(ErrorKind::DuplicateChild);
+        }
+        self.inner = Some(value);
+        Ok(())
+    }
+
+    fn get_single(self, name: &str) -> Result<T, ErrorKind> {
+        match self.inner {
+            Some(inner) => Ok(inner),
+            None => Err(ErrorKind::MissingChild {
+                field: name.to_owned(),
+            }),
+        }
+    }
+}
+
 #[async_trait::async_trait]
 impl<T: BoxRead + Sync> BoxPlaceholder<T> for SingleBoxPlaceholder<T> {
     type Output = T;
@@ -426,30 +637,24 @@ impl<T: BoxRead + Sync> BoxPlaceholder<T> for SingleBoxPlaceholder<T> {
         T::acceptable_tag(tag)
     }
 
+    fn acceptable_header(&self, header: &BoxHeader) -> bool {
+        T::acceptable_header(header)
+    }
+
     async fn read_body<R: AsyncRead + AsyncSeek + Unpin + Send>(
         &self,
         header: BoxHeader,
         reader: &mut Reader<R>,
-    ) -> io::Result<T> {
+    ) -> Result<T, Error> {
         T::read_body(header, reader).await
     }
 
-    fn push(&mut self, value: T) -> io::Result<()> {
-        if self.inner.is_some() {
-            return Err(io::Error::new(io::ErrorKind::AddrInUse, "already inserted"));
-        }
-        self.inner = Some(value);
-        Ok(())
+    fn push(&mut self, value: T) -> Result<(), ErrorKind> {
+        self.push_single(value)
     }
 
-    fn get(self, name: &str) -> io::Result<Self::Output> {
-        match self.inner {
-            Some(inner) => Ok(inner),
-            None => Err(io::Error::new(
-                io::ErrorKind::NotFound,
-                format!("field {} was not inserted", name),
-            )),
-        }
+    fn get(self, name: &str) -> Result<Self::Output, ErrorKind> {
+        self.get_single(name)
     }
 }
 
@@ -461,18 +666,22 @@ impl<T: BoxRead + Sync> BoxPlaceholder<T> for Option<T> {
         T::acceptable_tag(tag)
     }
 
+    fn acceptable_header(&self, header: &BoxHeader) -> bool {
+        T::acceptable_header(header)
+    }
+
     async fn read_body<R: AsyncRead + AsyncSeek + Unpin + Send>(
         &self,
         header: BoxHeader,
         reader: &mut Reader<R>,
-    ) -> io::Result<T> {
+    ) -> Result<T, Error> {
         T::read_body(header, reader).await
     }
-    fn push(&mut self, value: T) -> io::Result<()> {
+    fn push(&mut self, value: T) -> Result<(), ErrorKind> {
         *self = Some(value);
         Ok(())
     }
-    fn get(self, _: &str) -> io::Result<Self::Output> {
+    fn get(self, _: &str) -> Result<Self::Output, ErrorKind> {
         Ok(self)
     }
 }
@@ -485,18 +694,25 @@ impl<T: BoxRead + Sync> BoxPlaceholder<T> for Vec<T> {
         T::acceptable_tag(tag)
     }
 
+    fn acceptable_header(&self, header: &BoxHeader) -> bool {
+        T::acceptable_header(header)
+    }
+
     async fn read_body<R: AsyncRead + AsyncSeek + Unpin + Send>(
         &self,
         header: BoxHeader,
         reader: &mut Reader<R>,
-    ) -> io::Result<T> {
+    ) -> Result<T, Error> {
         T::read_body(header, reader).await
     }
-    fn push(&mut self, value: T) -> io::Result<()> {
+    fn next_index(&self) -> Option<usize> {
+        Some(self.len())
+    }
+    fn push(&mut self, value: T) -> Result<(), ErrorKind> {
         self.push(value);
         Ok(())
     }
-    fn get(self, _: &str) -> io::Result<Self::Output> {
+    fn get(self, _: &str) -> Result<Self::Output, ErrorKind> {
         Ok(self)
     }
 }
@@ -519,6 +735,95 @@ impl<T: BoxRead + Sync> BoxContainer<T, Vec<T>> for Vec<T> {
     }
 }
 
+/// Box types accepted by a child field of type `T`, `Option<T>` or `Vec<T>`
+pub trait ChildTags {
+    const TAGS: Option<&'static [[u8; 4]]>;
+}
+
+impl<T: BoxRead> ChildTags for T {
+    const TAGS: Option<&'static [[u8; 4]]> = T::TAGS;
+}
+
+impl<T: BoxRead> ChildTags for Option<T> {
+    const TAGS: Option<&'static [[u8; 4]]> = T::TAGS;
+}
+
+impl<T: BoxRead> ChildTags for Vec<T> {
+    const TAGS: Option<&'static [[u8; 4]]> = T::TAGS;
+}
+
+/// Whether two [`ChildTags::TAGS`] share a box type. Unknown sets never overlap.
+pub const fn tags_overlap(a: Option<&[[u8; 4]]>, b: Option<&[[u8; 4]]>) -> bool {
+    let (Some(a), Some(b)) = (a, b) else {
+        return false;
+    };
+    let mut i = 0;
+    while i < a.len() {
+        let mut j = 0;
+        while j < b.len() {
+            if u32::from_be_bytes(a[i]) == u32::from_be_bytes(b[j]) {
+                return true;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    false
+}
+
+/// Box of any type kept as its raw body, such as the children collected by `#[mp4(unknown)]` fields.
+/// Writing it emits the same bytes again.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RawBox {
+    pub header: BoxHeader,
+    /// Absolute offset of the header
+    pub offset: u64,
+    pub data: Bytes,
+}
+
+#[async_trait::async_trait]
+impl BoxRead for RawBox {
+    fn acceptable_tag(_: [u8; 4]) -> bool {
+        true
+    }
+
+    async fn read_body<R: AsyncRead + AsyncSeek + Unpin + Send>(
+        header: BoxHeader,
+        reader: &mut Reader<R>,
+    ) -> Result<Self, Error> {
+        let offset = reader.pos - header.header_size();
+        // the size comes from the file, so it is checked before allocating
+        visit::check_body_fits(&header, reader.remain()).map_err(|kind| reader.error(kind))?;
+        let mut data = vec![0; header.body_size()];
+        reader.read_exact(&mut data).await?;
+        Ok(Self {
+            header,
+            offset,
+            data: Bytes::from(data),
+        })
+    }
+}
+
+pub struct UnknownBox {
+    pub header: BoxHeader,
+}
+
+#[async_trait::async_trait]
+impl BoxRead for UnknownBox {
+    fn acceptable_tag(_: [u8; 4]) -> bool {
+        true
+    }
+
+    async fn read_body<R: AsyncRead + AsyncSeek + Unpin + Send>(
+        header: BoxHeader,
+        reader: &mut Reader<R>,
+    ) -> Result<Self, Error> {
+        reader.seek_from_current(header.body_size() as i64).await?;
+        Ok(Self { header })
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -589,23 +894,29 @@ mod test {
             vec![[0, 1], [2, 3]]
         );
     }
-}
-
-pub struct UnknownBox {
-    pub header: BoxHeader,
-}
 
-#[async_trait::async_trait]
-impl BoxRead for UnknownBox {
-    fn acceptable_tag(_: [u8; 4]) -> bool {
-        true
-    }
-
-    async fn read_body<R: AsyncRead + AsyncSeek + Unpin + Send>(
-        header: BoxHeader,
-        reader: &mut Reader<R>,
-    ) -> Result<Self, io::Error> {
-        reader.seek_from_current(header.body_size() as i64).await?;
-        Ok(Self { header })
+    #[tokio::test]
+    async fn test_raw_box_size() {
+        // largesize box claiming far more bytes than the file has
+        let mut src = vec![0, 0, 0, 1, b'f', b'r', b'e', b'e'];
+        src.extend_from_slice(&u64::MAX.to_be_bytes());
+        src.extend_from_slice(&[0; 4]);
+        let limit = src.len() as u64;
+        let err = RawBox::read_attr(&mut Reader::new(io::Cursor::new(src.clone()), limit))
+            .await
+            .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Truncated { .. }));
+        let err =
+            <RawBox as blocking::AttrRead>::read_attr(&mut blocking::Reader::from_slice(&src))
+                .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Truncated { .. }));
+    }
+
+    #[test]
+    fn test_tags_overlap() {
+        assert!(tags_overlap(Some(&[*b"moov", *b"trak"]), Some(&[*b"trak"])));
+        assert!(!tags_overlap(Some(&[*b"moov"]), Some(&[*b"trak"])));
+        assert!(!tags_overlap(None, Some(&[*b"trak"])));
+        assert_eq!(<Option<RawBox> as ChildTags>::TAGS, None);
     }
 }
@@ -0,0 +1,216 @@
+//! Traits reading boxes out of an in-memory buffer, with fields borrowing it.
+//!
+//! `#[derive(BoxRead)]`, `#[derive(AttrRead)]` and `#[derive(RootRead)]` on a type marked `#[mp4(borrow)]` implement
+//! these traits instead of the async and blocking ones, so its `&'a [u8]` fields are slices of the buffer read by
+//! [`Reader`]. Every type implementing the blocking traits implements these as well and can be a field of such a type.
+//! ```rust
+//! use movparse_box::{slice::{AttrRead, Reader}, BoxHeader};
+//!
+//! let src = [0, 0, 0, 12, b'f', b'r', b'e', b'e', 1, 2, 3, 4];
+//! let mut reader = Reader::from_slice(&src);
+//! let header = BoxHeader::read_blocking(&mut reader).unwrap();
+//! reader.set_limit(header.body_size() as u64);
+//! let body: &[u8] = AttrRead::read_attr(&mut reader).unwrap();
+//! assert_eq!(body.as_ptr(), src[8..].as_ptr());
+//! ```
+use crate::{blocking, BoxHeader, Error, ErrorKind, SingleBoxPlaceholder, U32Tag};
+
+/// Blocking reader over a buffer borrowed for `'a`
+pub type Reader<'a> = blocking::Reader<&'a [u8]>;
+
+pub trait AttrRead<'a>: Sized {
+    fn read_attr(reader: &mut Reader<'a>) -> Result<Self, Error>;
+}
+
+pub trait BoxRead<'a>: Sized {
+    /// Box types accepted by `acceptable_tag`, `None` if not known at compile time
+    const TAGS: Option<&'static [[u8; 4]]> = None;
+    fn acceptable_tag(tag: [u8; 4]) -> bool;
+    /// Whether a box with `header` is read as `Self`. `uuid` boxes override this to check the extended type.
+    fn acceptable_header(header: &BoxHeader) -> bool {
+        Self::acceptable_tag(header.id)
+    }
+    fn read_body(header: BoxHeader, reader: &mut Reader<'a>) -> Result<Self, Error>;
+}
+
+pub trait RootRead<'a>: Sized {
+    fn read(reader: &mut Reader<'a>) -> Result<Self, Error>;
+}
+
+impl<'a, T: blocking::AttrRead> AttrRead<'a> for T {
+    fn read_attr(reader: &mut Reader<'a>) -> Result<Self, Error> {
+        blocking::AttrRead::read_attr(reader)
+    }
+}
+
+impl<'a, T: blocking::BoxRead> BoxRead<'a> for T {
+    const TAGS: Option<&'static [[u8; 4]]> = <T as blocking::BoxRead>::TAGS;
+
+    fn acceptable_tag(tag: [u8; 4]) -> bool {
+        <T as blocking::BoxRead>::acceptable_tag(tag)
+    }
+
+    fn acceptable_header(header: &BoxHeader) -> bool {
+        <T as blocking::BoxRead>::acceptable_header(header)
+    }
+
+    fn read_body(header: BoxHeader, reader: &mut Reader<'a>) -> Result<Self, Error> {
+        blocking::BoxRead::read_body(header, reader)
+    }
+}
+
+impl<'a, T: blocking::RootRead> RootRead<'a> for T {
+    fn read(reader: &mut Reader<'a>) -> Result<Self, Error> {
+        blocking::RootRead::read(reader)
+    }
+}
+
+/// The rest of the body, borrowed from the buffer
+impl<'a: 'b, 'b> AttrRead<'a> for &'b [u8] {
+    fn read_attr(reader: &mut Reader<'a>) -> Result<Self, Error> {
+        reader.read_slice(reader.remain() as usize)
+    }
+}
+
+/// Reads a box, header included, as a field. The derived [`AttrRead`] of boxes marked `#[mp4(borrow)]` calls this.
+pub fn read_box<'a, T: BoxRead<'a>>(reader: &mut Reader<'a>) -> Result<T, Error> {
+    let offset = reader.pos;
+    let header = BoxHeader::read_blocking(reader)?;
+    if T::acceptable_header(&header) {
+        let parent = reader.path.clone();
+        reader.path = parent.join(header.id, None);
+        let result = T::read_body(header, reader);
+        reader.path = parent;
+        result
+    } else {
+        let tag = U32Tag { raw: header.id };
+        Err(Error::new(
+            ErrorKind::UnexpectedTag { tag },
+            reader.path.clone(),
+            offset,
+        ))
+    }
+}
+
+pub trait BoxPlaceholder<'a, T: BoxRead<'a>> {
+    type Output;
+    fn push(&mut self, value: T) -> Result<(), ErrorKind>;
+    fn get(self, name: &str) -> Result<Self::Output, ErrorKind>;
+    fn acceptable_tag(&self, tag: [u8; 4]) -> bool {
+        T::acceptable_tag(tag)
+    }
+    fn acceptable_header(&self, header: &BoxHeader) -> bool {
+        T::acceptable_header(header)
+    }
+    /// Index of the next pushed box among its siblings, shown in error paths
+    fn next_index(&self) -> Option<usize> {
+        None
+    }
+
+    /// Reads the body of a child box and pushes it, extending `reader.path` while it is parsed
+    fn read_child(&mut self, header: BoxHeader, reader: &mut Reader<'a>) -> Result<(), Error> {
+        let parent = reader.path.clone();
+        reader.path = parent.join(header.id, self.next_index());
+        let result = T::read_body(header, reader)
+            .and_then(|value| self.push(value).map_err(|kind| reader.error(kind)));
+        reader.path = parent;
+        result
+    }
+}
+
+pub trait BoxContainer<'a, T: BoxRead<'a>, D: BoxPlaceholder<'a, T>> {
+    fn placeholder() -> D;
+}
+
+impl<'a, T: BoxRead<'a>> BoxPlaceholder<'a, T> for SingleBoxPlaceholder<T> {
+    type Output = T;
+
+    fn push(&mut self, value: T) -> Result<(), ErrorKind> {
+        self.push_single(value)
+    }
+
+    fn get(self, name: &str) -> Result<Self::Output, ErrorKind> {
+        self.get_single(name)
+    }
+}
+
+impl<'a, T: BoxRead<'a>> BoxPlaceholder<'a, T> for Option<T> {
+    type Output = Option<T>;
+
+    fn push(&mut self, value: T) -> Result<(), ErrorKind> {
+        *self = Some(value);
+        Ok(())
+    }
+
+    fn get(self, _: &str) -> Result<Self::Output, ErrorKind> {
+        Ok(self)
+    }
+}
+
+impl<'a, T: BoxRead<'a>> BoxPlaceholder<'a, T> for Vec<T> {
+    type Output = Vec<T>;
+
+    fn next_index(&self) -> Option<usize> {
+        Some(self.len())
+    }
+
+    fn push(&mut self, value: T) -> Result<(), ErrorKind> {
+        self.push(value);
+        Ok(())
+    }
+
+    fn get(self, _: &str) -> Result<Self::Output, ErrorKind> {
+        Ok(self)
+    }
+}
+
+impl<'a, T: BoxRead<'a>> BoxContainer<'a, T, SingleBoxPlaceholder<T>> for T {
+    fn placeholder() -> SingleBoxPlaceholder<T> {
+        SingleBoxPlaceholder { inner: None }
+    }
+}
+
+impl<'a, T: BoxRead<'a>> BoxContainer<'a, T, Option<T>> for Option<T> {
+    fn placeholder() -> Option<T> {
+        None
+    }
+}
+
+impl<'a, T: BoxRead<'a>> BoxContainer<'a, T, Vec<T>> for Vec<T> {
+    fn placeholder() -> Vec<T> {
+        Vec::new()
+    }
+}
+
+/// Box types accepted by a child field of type `T`, `Option<T>` or `Vec<T>`, whose type may borrow the buffer.
+/// Counterpart of [`crate::ChildTags`] for the checks of the derived readers.
+pub trait ChildTags {
+    const TAGS: Option<&'static [[u8; 4]]>;
+}
+
+impl<T: BoxRead<'static>> ChildTags for T {
+    const TAGS: Option<&'static [[u8; 4]]> = T::TAGS;
+}
+
+impl<T: BoxRead<'static>> ChildTags for Option<T> {
+    const TAGS: Option<&'static [[u8; 4]]> = T::TAGS;
+}
+
+impl<T: BoxRead<'static>> ChildTags for Vec<T> {
+    const TAGS: Option<&'static [[u8; 4]]> = T::TAGS;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_borrowed_rest() {
+        let src = [0u8, 1, 2, 3, 4];
+        let mut reader = Reader::from_slice(&src);
+        assert_eq!(<u16 as AttrRead>::read_attr(&mut reader).unwrap(), 1);
+        let rest = <&[u8]>::read_attr(&mut reader).unwrap();
+        assert_eq!(rest, [2, 3, 4]);
+        assert_eq!(rest.as_ptr(), src[2..].as_ptr());
+    }
+}
@@ -0,0 +1,281 @@
+//! Forward-only parsing for inputs that cannot seek, such as pipes, stdin or sockets.
+//!
+//! [`ForwardOnly`] implements seeking by reading and discarding, so the existing [`crate::Reader`] and
+//! derived box types work on it as long as nothing seeks backward.
+//! [`Stream`] walks the top-level boxes one at a time, which lets fragmented files be parsed
+//! incrementally as each `moof`/`mdat` pair arrives.
+//! ```rust
+//! use movparse_box::{stream::Stream, UnknownBox};
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let src: &[u8] = &[0, 0, 0, 12, b'f', b'r', b'e', b'e', 0, 0, 0, 0];
+//! let mut stream = Stream::new(src);
+//! while let Some(header) = stream.next_header().await.unwrap() {
+//!     let free: UnknownBox = stream.read_body(header).await.unwrap();
+//!     assert_eq!(&free.header.id, b"free");
+//! }
+//! # }
+//! ```
+use std::{
+    io::{self, BufRead, Read, Seek, SeekFrom},
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncSeek, AsyncSeekExt, ReadBuf};
+
+use crate::{BoxHeader, BoxPath, BoxRead, Error, Reader};
+
+/// Adapter over a buffered reader that only moves forward.
+/// Seeking ahead discards bytes, seeking backward fails with [`io::ErrorKind::Unsupported`].
+pub struct ForwardOnly<R> {
+    inner: R,
+    pos: u64,
+    target: Option<u64>,
+}
+
+impl<R> ForwardOnly<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            pos: 0,
+            target: None,
+        }
+    }
+
+    /// Number of bytes consumed from the inner reader
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn target(&self, position: SeekFrom) -> io::Result<u64> {
+        let target = match position {
+            SeekFrom::Start(target) => Some(target),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+            SeekFrom::End(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "cannot seek from the end of a forward-only stream",
+                ))
+            }
+        };
+        match target {
+            Some(target) if target >= self.pos => Ok(target),
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "cannot seek backward from {} in a forward-only stream",
+                    self.pos
+                ),
+            )),
+        }
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> ForwardOnly<R> {
+    /// Returns `true` if the inner reader has no more bytes
+    pub async fn at_eof(&mut self) -> io::Result<bool> {
+        Ok(self.inner.fill_buf().await?.is_empty())
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncRead for ForwardOnly<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        this.pos += (buf.filled().len() - before) as u64;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<R: AsyncBufRead + Unpin> AsyncSeek for ForwardOnly<R> {
+    fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+        let this = self.get_mut();
+        this.target = Some(this.target(position)?);
+        Ok(())
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        let this = self.get_mut();
+        while let Some(target) = this.target {
+            if this.pos == target {
+                this.target = None;
+                break;
+            }
+            let available = match ready!(Pin::new(&mut this.inner).poll_fill_buf(cx)) {
+                Ok(available) => available.len(),
+                Err(e) => {
+                    this.target = None;
+                    return Poll::Ready(Err(e));
+                }
+            };
+            if available == 0 {
+                this.target = None;
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("stream ended at {} while skipping to {}", this.pos, target),
+                )));
+            }
+            let consumed = available.min((target - this.pos) as usize);
+            Pin::new(&mut this.inner).consume(consumed);
+            this.pos += consumed as u64;
+        }
+        Poll::Ready(Ok(this.pos))
+    }
+}
+
+impl<R: BufRead> ForwardOnly<R> {
+    /// Returns `true` if the inner reader has no more bytes
+    pub fn at_eof_blocking(&mut self) -> io::Result<bool> {
+        Ok(self.inner.fill_buf()?.is_empty())
+    }
+}
+
+impl<R: BufRead> Read for ForwardOnly<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let size = self.inner.read(buf)?;
+        self.pos += size as u64;
+        Ok(size)
+    }
+}
+
+impl<R: BufRead> Seek for ForwardOnly<R> {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let target = self.target(position)?;
+        let skipped = io::copy(
+            &mut (&mut self.inner).take(target - self.pos),
+            &mut io::sink(),
+        )?;
+        self.pos += skipped;
+        if self.pos < target {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("stream ended at {} while skipping to {}", self.pos, target),
+            ));
+        }
+        Ok(self.pos)
+    }
+}
+
+/// Top-level boxes of a forward-only input, read one by one as they arrive
+pub struct Stream<R: AsyncBufRead + Unpin + Send> {
+    pub reader: Reader<ForwardOnly<R>>,
+    /// Offset of the next box, `None` after a box extending to the end of the input
+    next: Option<u64>,
+}
+
+impl<R: AsyncBufRead + Unpin + Send> Stream<R> {
+    pub fn new(inner: R) -> Self {
+        let mut reader = Reader::new(ForwardOnly::new(inner), 0);
+        reader.clear_limit();
+        Self {
+            reader,
+            next: Some(0),
+        }
+    }
+
+    /// Reads the header of the next top-level box, or returns `None` when the input ends between boxes.
+    /// Whatever was not read of the previous box is discarded first. A box of size 0 extends to the end
+    /// of the input, so nothing follows it.
+    pub async fn next_header(&mut self) -> Result<Option<BoxHeader>, Error> {
+        let Some(next) = self.next else {
+            return Ok(None);
+        };
+        self.reader.pos = next;
+        let at_eof = async {
+            let mut inner = self.reader.inner.lock().await;
+            inner.seek(SeekFrom::Start(next)).await?;
+            inner.at_eof().await
+        };
+        if at_eof.await.map_err(|e| self.reader.error(e))? {
+            return Ok(None);
+        }
+        let header = BoxHeader::read(&mut self.reader).await?;
+        // without a limit, the body of a box of size 0 takes everything the reader could give
+        let open_ended = header.size - header.header_size() >= self.reader.remain() as u64;
+        self.next = (!open_ended).then(|| next + header.size);
+        Ok(Some(header))
+    }
+
+    /// Parses the body of the box returned by [`Stream::next_header`]
+    pub async fn read_body<T: BoxRead>(&mut self, header: BoxHeader) -> Result<T, Error> {
+        self.reader.path = BoxPath::default().join(header.id, None);
+        let result = T::read_body(header, &mut self.reader).await;
+        self.reader.path = BoxPath::default();
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{AttrRead, UnknownBox};
+
+    #[tokio::test]
+    async fn test_forward_only() {
+        let src: &[u8] = &[0u8, 1, 2, 3, 4, 5];
+        let mut reader = Reader::new(ForwardOnly::new(src), 6);
+        reader.seek_from_current(2).await.unwrap();
+        assert_eq!(<[u8; 2]>::read_attr(&mut reader).await.unwrap(), [2, 3]);
+        reader.seek_from_start(1).await.unwrap();
+        assert!(<[u8; 2]>::read_attr(&mut reader).await.is_err());
+    }
+
+    #[test]
+    fn test_forward_only_blocking() {
+        let src: &[u8] = &[0u8, 1, 2, 3, 4, 5];
+        let mut reader = crate::blocking::Reader::new(ForwardOnly::new(src), 6);
+        reader.seek_from_current(2).unwrap();
+        assert_eq!(
+            <[u8; 2] as crate::blocking::AttrRead>::read_attr(&mut reader).unwrap(),
+            [2, 3]
+        );
+        reader.seek_from_start(1).unwrap();
+        assert!(<[u8; 2] as crate::blocking::AttrRead>::read_attr(&mut reader).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        let src: &[u8] = &[
+            0, 0, 0, 12, b'f', b'r', b'e', b'e', 0, 0, 0, 0, //
+            0, 0, 0, 10, b's', b'k', b'i', b'p', 1, 2, //
+            0, 0, 0, 8, b'f', b'r', b'e', b'e',
+        ];
+        let mut stream = Stream::new(src);
+        // bodies left unread are discarded by next_header
+        let header = stream.next_header().await.unwrap().unwrap();
+        assert_eq!(&header.id, b"free");
+        let header = stream.next_header().await.unwrap().unwrap();
+        assert_eq!(&header.id, b"skip");
+        let header = stream.next_header().await.unwrap().unwrap();
+        let free: UnknownBox = stream.read_body(header).await.unwrap();
+        assert_eq!(free.header.size, 8);
+        assert!(stream.next_header().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stream_open_ended() {
+        let src: &[u8] = &[
+            0, 0, 0, 8, b'f', b'r', b'e', b'e', //
+            0, 0, 0, 0, b'm', b'd', b'a', b't', 1, 2, 3,
+        ];
+        let mut stream = Stream::new(src);
+        let header = stream.next_header().await.unwrap().unwrap();
+        assert_eq!(&header.id, b"free");
+        // an mdat of size 0 runs to the end of the input and is the last box
+        let header = stream.next_header().await.unwrap().unwrap();
+        assert_eq!(&header.id, b"mdat");
+        assert!(stream.next_header().await.unwrap().is_none());
+        assert!(stream.next_header().await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,125 @@
+use tokio::io::{AsyncRead, AsyncSeek};
+
+use crate::{
+    blocking,
+    visit::{check_body_fits, MIN_BOX_SIZE},
+    BoxHeader, Error, Reader,
+};
+
+const FTYP: [u8; 4] = *b"ftyp";
+/// Bytes of `ftyp` read for brands, the rest is skipped
+const MAX_FTYP_SIZE: usize = 4096;
+
+/// Types of the top-level boxes of a file and the brands of its `ftyp`.
+/// Derived `RootRead` enums scan this first to pick the variant to read.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TopLevel {
+    pub ids: Vec<[u8; 4]>,
+    /// Major brand followed by the compatible brands, empty without `ftyp`
+    pub brands: Vec<[u8; 4]>,
+}
+
+impl TopLevel {
+    /// Scans with a clone of `reader`, which is left where it was
+    pub async fn scan<R: AsyncRead + AsyncSeek + Unpin + Send>(
+        reader: &Reader<R>,
+    ) -> Result<Self, Error> {
+        let mut reader = reader.clone();
+        let mut top = Self::default();
+        while reader.remain() >= MIN_BOX_SIZE {
+            let header = BoxHeader::read(&mut reader).await?;
+            check_body_fits(&header, reader.remain()).map_err(|kind| reader.error(kind))?;
+            if header.id == FTYP && top.brands.is_empty() {
+                let len = header.body_size().min(MAX_FTYP_SIZE);
+                let mut body = vec![0; len];
+                reader.read_exact(&mut body).await?;
+                top.push_brands(&body);
+                reader
+                    .seek_from_current((header.body_size() - len) as i64)
+                    .await?;
+            } else {
+                reader.seek_from_current(header.body_size() as i64).await?;
+            }
+            top.ids.push(header.id);
+        }
+        Ok(top)
+    }
+
+    pub fn scan_blocking<R: blocking::Source>(reader: &blocking::Reader<R>) -> Result<Self, Error> {
+        let mut reader = reader.clone();
+        let mut top = Self::default();
+        while reader.remain() >= MIN_BOX_SIZE {
+            let header = BoxHeader::read_blocking(&mut reader)?;
+            check_body_fits(&header, reader.remain()).map_err(|kind| reader.error(kind))?;
+            if header.id == FTYP && top.brands.is_empty() {
+                let len = header.body_size().min(MAX_FTYP_SIZE);
+                let body = reader.read_bytes(len)?;
+                top.push_brands(&body);
+                reader.seek_from_current((header.body_size() - len) as i64)?;
+            } else {
+                reader.seek_from_current(header.body_size() as i64)?;
+            }
+            top.ids.push(header.id);
+        }
+        Ok(top)
+    }
+
+    pub fn contains(&self, id: [u8; 4]) -> bool {
+        self.ids.contains(&id)
+    }
+
+    /// Whether `brand` is the major or one of the compatible brands
+    pub fn has_brand(&self, brand: [u8; 4]) -> bool {
+        self.brands.contains(&brand)
+    }
+
+    fn push_brands(&mut self, ftyp: &[u8]) {
+        // the minor version between the major and compatible brands is not a brand
+        let major = ftyp.chunks_exact(4).take(1);
+        let compatible = ftyp.get(8..).unwrap_or_default().chunks_exact(4);
+        self.brands.extend(
+            major
+                .chain(compatible)
+                .map(|brand| [brand[0], brand[1], brand[2], brand[3]]),
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ErrorKind;
+
+    #[test]
+    fn test_scan() {
+        let src = [
+            0, 0, 0, 20, b'f', b't', b'y', b'p', b'i', b's', b'o', b'6', 0, 0, 0, 0, b'd', b'a',
+            b's', b'h', //
+            0, 0, 0, 8, b'm', b'o', b'o', b'v', //
+            0, 0, 0, 8, b'm', b'o', b'o', b'f',
+        ];
+        let reader = blocking::Reader::from_slice(&src);
+        let top = TopLevel::scan_blocking(&reader).unwrap();
+        assert_eq!(reader.pos, 0);
+        assert_eq!(top.ids, [*b"ftyp", *b"moov", *b"moof"]);
+        assert_eq!(top.brands, [*b"iso6", *b"dash"]);
+        assert!(top.has_brand(*b"dash") && !top.has_brand([0; 4]));
+        assert!(top.contains(*b"moof"));
+
+        // a bogus ftyp size is rejected before anything is allocated for it
+        let mut huge = src.to_vec();
+        huge[..4].copy_from_slice(&0xffff_fff0u32.to_be_bytes());
+        let err = TopLevel::scan_blocking(&blocking::Reader::from_slice(&huge)).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Truncated { .. }));
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let err = rt
+            .block_on(TopLevel::scan(&Reader::new(
+                std::io::Cursor::new(huge.clone()),
+                huge.len() as u64,
+            )))
+            .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Truncated { .. }));
+    }
+}
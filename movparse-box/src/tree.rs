@@ -0,0 +1,202 @@
+//! Untyped view of a file as a tree of boxes, for files without (or rejected by) typed structs.
+//! ```rust
+//! use movparse_box::{blocking::Reader, tree::{BoxTree, CONTAINERS}};
+//!
+//! let src = [0, 0, 0, 16, b'm', b'o', b'o', b'v', 0, 0, 0, 8, b'f', b'r', b'e', b'e'];
+//! let tree = BoxTree::read_blocking(&mut Reader::from_slice(&src), CONTAINERS).unwrap();
+//! assert_eq!(&tree.nodes[0].children[0].header.id, b"free");
+//! ```
+use std::{fmt, future::Future, pin::Pin};
+
+use tokio::io::{AsyncRead, AsyncSeek};
+
+use crate::{
+    blocking,
+    error::write_fourcc,
+    visit::{check_body_fits, MIN_BOX_SIZE},
+    BoxHeader, BoxRead, Error, Reader, UnknownBox,
+};
+
+/// Box types whose body is a sequence of child boxes
+pub const CONTAINERS: &[[u8; 4]] = &[
+    *b"moov", *b"trak", *b"edts", *b"mdia", *b"minf", *b"dinf", *b"stbl", *b"udta", *b"tref",
+    *b"mvex", *b"moof", *b"traf", *b"mfra", *b"sinf", *b"schi", *b"meco", *b"gmhd",
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BoxNode {
+    pub header: BoxHeader,
+    /// Absolute offset of the header
+    pub offset: u64,
+    /// Empty unless the box type is one of the containers the tree was read with
+    pub children: Vec<BoxNode>,
+}
+
+impl BoxNode {
+    pub fn header_size(&self) -> u64 {
+        self.header.header_size()
+    }
+
+    pub fn body_size(&self) -> u64 {
+        self.header.body_size() as u64
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        f.write_fmt(format_args!("{:width$}", "", width = depth * 2))?;
+        write_fourcc(f, self.header.id)?;
+        f.write_fmt(format_args!(
+            " @{} ({} + {} bytes)\n",
+            self.offset,
+            self.header_size(),
+            self.body_size()
+        ))?;
+        for child in &self.children {
+            child.fmt_indented(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Top-level boxes of a file. `Display` dumps the tree, one box per line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BoxTree {
+    pub nodes: Vec<BoxNode>,
+}
+
+impl BoxTree {
+    /// Reads boxes until the limit of `reader`, descending into the box types in `containers`
+    pub async fn read<R: AsyncRead + AsyncSeek + Unpin + Send>(
+        reader: &mut Reader<R>,
+        containers: &[[u8; 4]],
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            nodes: read_nodes(reader, containers).await?,
+        })
+    }
+
+    pub fn read_blocking<R: blocking::Source>(
+        reader: &mut blocking::Reader<R>,
+        containers: &[[u8; 4]],
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            nodes: read_nodes_blocking(reader, containers)?,
+        })
+    }
+}
+
+impl fmt::Display for BoxTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for node in &self.nodes {
+            node.fmt_indented(f, 0)?;
+        }
+        Ok(())
+    }
+}
+
+type NodesFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<BoxNode>, Error>> + Send + 'a>>;
+
+fn read_nodes<'a, R: AsyncRead + AsyncSeek + Unpin + Send>(
+    reader: &'a mut Reader<R>,
+    containers: &'a [[u8; 4]],
+) -> NodesFuture<'a> {
+    Box::pin(async move {
+        let mut nodes = Vec::new();
+        while reader.remain() >= MIN_BOX_SIZE {
+            let offset = reader.pos;
+            let header = BoxHeader::read(reader).await?;
+            let parent = reader.path.clone();
+            reader.path = parent.join(header.id, None);
+            check_body_fits(&header, reader.remain()).map_err(|kind| reader.error(kind))?;
+            let children = if containers.contains(&header.id) {
+                let mut reader2 = reader.clone();
+                reader2.set_limit(header.body_size() as u64);
+                let children = read_nodes(&mut reader2, containers).await?;
+                reader.seek_from_current(header.body_size() as i64).await?;
+                children
+            } else {
+                UnknownBox::read_body(header, reader).await?;
+                Vec::new()
+            };
+            reader.path = parent;
+            nodes.push(BoxNode {
+                header,
+                offset,
+                children,
+            });
+        }
+        Ok(nodes)
+    })
+}
+
+fn read_nodes_blocking<R: blocking::Source>(
+    reader: &mut blocking::Reader<R>,
+    containers: &[[u8; 4]],
+) -> Result<Vec<BoxNode>, Error> {
+    let mut nodes = Vec::new();
+    while reader.remain() >= MIN_BOX_SIZE {
+        let offset = reader.pos;
+        let header = BoxHeader::read_blocking(reader)?;
+        let parent = reader.path.clone();
+        reader.path = parent.join(header.id, None);
+        check_body_fits(&header, reader.remain()).map_err(|kind| reader.error(kind))?;
+        let children = if containers.contains(&header.id) {
+            let mut reader2 = reader.clone();
+            reader2.set_limit(header.body_size() as u64);
+            let children = read_nodes_blocking(&mut reader2, containers)?;
+            reader.seek_from_current(header.body_size() as i64)?;
+            children
+        } else {
+            <UnknownBox as blocking::BoxRead>::read_body(header, reader)?;
+            Vec::new()
+        };
+        reader.path = parent;
+        nodes.push(BoxNode {
+            header,
+            offset,
+            children,
+        });
+    }
+    Ok(nodes)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const SRC: [u8; 44] = [
+        0, 0, 0, 12, b'f', b't', b'y', b'p', b'i', b's', b'o', b'm', //
+        0, 0, 0, 32, b'm', b'o', b'o', b'v', //
+        0, 0, 0, 12, b'u', b'd', b't', b'a', 0, 0, 0, 0, //
+        0, 0, 0, 12, b'm', b'v', b'h', b'd', 1, 2, 3, 4,
+    ];
+
+    #[tokio::test]
+    async fn test_tree() {
+        let mut reader = Reader::new(Cursor::new(SRC), SRC.len() as u64);
+        let tree = BoxTree::read(&mut reader, CONTAINERS).await.unwrap();
+        assert_eq!(
+            tree.to_string(),
+            "ftyp @0 (8 + 4 bytes)\nmoov @12 (8 + 24 bytes)\n  udta @20 (8 + 4 bytes)\n  mvhd @32 (8 + 4 bytes)\n"
+        );
+        assert_eq!(reader.pos, SRC.len() as u64);
+    }
+
+    #[test]
+    fn test_tree_blocking() {
+        let mut reader = blocking::Reader::from_slice(&SRC);
+        let tree = BoxTree::read_blocking(&mut reader, &[*b"moov"]).unwrap();
+        assert_eq!(tree.nodes[1].children[1].offset, 32);
+        assert_eq!(tree.nodes[1].children[1].body_size(), 4);
+
+        // a child overrunning its parent is reported at its path
+        let mut broken = SRC;
+        broken[35] = 16;
+        let err = BoxTree::read_blocking(&mut blocking::Reader::from_slice(&broken), CONTAINERS)
+            .unwrap_err();
+        assert_eq!(err.path.to_string(), "moov/mvhd");
+    }
+}
@@ -0,0 +1,176 @@
+//! Event-driven walk over the boxes of a file.
+//!
+//! [`walk`] reports every box to a [`BoxVisitor`], which decides per box whether to descend into it,
+//! hand its body to [`BoxVisitor::leaf_body`] (e.g. to parse it with a typed [`crate::BoxRead`] impl),
+//! skip it, or stop the walk. Only the boxes the visitor asks for are read.
+use std::{future::Future, pin::Pin};
+
+use tokio::io::{AsyncRead, AsyncSeek};
+
+use crate::{BoxHeader, BoxPath, Error, ErrorKind, Reader};
+
+/// What to do with the box passed to `enter_box`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Visit {
+    /// Walk the body as a sequence of child boxes
+    Descend,
+    /// Pass the body to `leaf_body`
+    Body,
+    Skip,
+    /// End the walk without visiting anything else. `exit_box` is not called for open boxes.
+    Stop,
+}
+
+#[async_trait::async_trait]
+pub trait BoxVisitor: Send {
+    /// `path` includes the entered box, `offset` is the absolute offset of its header
+    fn enter_box(&mut self, path: &BoxPath, header: &BoxHeader, offset: u64) -> Visit;
+
+    /// Called for boxes entered with [`Visit::Body`]. `reader` is positioned at the body and limited to it;
+    /// whatever is left unread is skipped afterwards.
+    async fn leaf_body<R: AsyncRead + AsyncSeek + Unpin + Send>(
+        &mut self,
+        _header: BoxHeader,
+        _reader: &mut Reader<R>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn exit_box(&mut self, _path: &BoxPath, _header: &BoxHeader) {}
+}
+
+/// Errors when a box claims more bytes than its parent (or the reader limit) has left
+pub(crate) fn check_body_fits(header: &BoxHeader, remain: i64) -> Result<(), ErrorKind> {
+    let requested = header.body_size() as u64;
+    let available = remain.max(0) as u64;
+    if requested > available {
+        return Err(ErrorKind::Truncated {
+            requested,
+            available,
+        });
+    }
+    Ok(())
+}
+
+// a box needs at least 8 bytes, shorter trailers (e.g. the zero terminator of `udta`) are ignored
+pub(crate) const MIN_BOX_SIZE: i64 = 8;
+
+/// Walks boxes until the limit of `reader`. Returns `false` if the visitor stopped the walk.
+pub async fn walk<R: AsyncRead + AsyncSeek + Unpin + Send, V: BoxVisitor>(
+    reader: &mut Reader<R>,
+    visitor: &mut V,
+) -> Result<bool, Error> {
+    walk_boxed(reader, visitor).await
+}
+
+type WalkFuture<'a> = Pin<Box<dyn Future<Output = Result<bool, Error>> + Send + 'a>>;
+
+fn walk_boxed<'a, R: AsyncRead + AsyncSeek + Unpin + Send, V: BoxVisitor>(
+    reader: &'a mut Reader<R>,
+    visitor: &'a mut V,
+) -> WalkFuture<'a> {
+    Box::pin(async move {
+        while reader.remain() >= MIN_BOX_SIZE {
+            let offset = reader.pos;
+            let header = BoxHeader::read(reader).await?;
+            let parent = reader.path.clone();
+            reader.path = parent.join(header.id, None);
+            check_body_fits(&header, reader.remain()).map_err(|kind| reader.error(kind))?;
+            let mut reader2 = reader.clone();
+            reader2.set_limit(header.body_size() as u64);
+            match visitor.enter_box(&reader.path, &header, offset) {
+                Visit::Descend => {
+                    if !walk_boxed(&mut reader2, visitor).await? {
+                        return Ok(false);
+                    }
+                }
+                Visit::Body => visitor.leaf_body(header, &mut reader2).await?,
+                Visit::Skip => (),
+                Visit::Stop => return Ok(false),
+            }
+            visitor.exit_box(&reader.path, &header);
+            reader.seek_from_current(header.body_size() as i64).await?;
+            reader.path = parent;
+        }
+        Ok(true)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::AttrRead;
+
+    const SRC: [u8; 44] = [
+        0, 0, 0, 12, b'f', b't', b'y', b'p', b'i', b's', b'o', b'm', //
+        0, 0, 0, 32, b'm', b'o', b'o', b'v', //
+        0, 0, 0, 12, b'u', b'd', b't', b'a', 0, 0, 0, 0, //
+        0, 0, 0, 12, b'm', b'v', b'h', b'd', 0, 0, 3, 232,
+    ];
+
+    #[derive(Default)]
+    struct Events {
+        log: Vec<String>,
+        timescale: Option<u32>,
+    }
+
+    #[async_trait::async_trait]
+    impl BoxVisitor for Events {
+        fn enter_box(&mut self, path: &BoxPath, _: &BoxHeader, offset: u64) -> Visit {
+            self.log.push(format!("enter {} @{}", path, offset));
+            match &path.segments().last().unwrap().0 {
+                b"moov" => Visit::Descend,
+                b"mvhd" => Visit::Body,
+                b"free" => Visit::Stop,
+                _ => Visit::Skip,
+            }
+        }
+
+        async fn leaf_body<R: AsyncRead + AsyncSeek + Unpin + Send>(
+            &mut self,
+            _: BoxHeader,
+            reader: &mut Reader<R>,
+        ) -> Result<(), Error> {
+            self.timescale = Some(u32::read_attr(reader).await?);
+            Ok(())
+        }
+
+        fn exit_box(&mut self, path: &BoxPath, _: &BoxHeader) {
+            self.log.push(format!("exit {}", path));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_walk() {
+        let mut reader = Reader::new(Cursor::new(SRC), SRC.len() as u64);
+        let mut events = Events::default();
+        assert!(walk(&mut reader, &mut events).await.unwrap());
+        assert_eq!(reader.pos, SRC.len() as u64);
+        assert_eq!(events.timescale, Some(1000));
+        assert_eq!(
+            events.log,
+            [
+                "enter ftyp @0",
+                "exit ftyp",
+                "enter moov @12",
+                "enter moov/udta @20",
+                "exit moov/udta",
+                "enter moov/mvhd @32",
+                "exit moov/mvhd",
+                "exit moov",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_walk_stop() {
+        let mut src = SRC.to_vec();
+        src[4..8].copy_from_slice(b"free");
+        let mut reader = Reader::new(Cursor::new(src), SRC.len() as u64);
+        let mut events = Events::default();
+        assert!(!walk(&mut reader, &mut events).await.unwrap());
+        assert_eq!(events.log, ["enter free @0"]);
+    }
+}
@@ -0,0 +1,331 @@
+use std::{io, marker::PhantomData};
+
+use bytes::Bytes;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::{BoxHeader, RawBox, RawString, U32Tag};
+
+#[async_trait::async_trait]
+pub trait AttrWrite: Sync {
+    /// Number of bytes written by [`AttrWrite::write_attr`]
+    fn attr_size(&self) -> u64;
+    async fn write_attr<W: AsyncWrite + Unpin + Send>(&self, writer: &mut W) -> io::Result<()>;
+}
+
+#[async_trait::async_trait]
+pub trait BoxWrite: Sync {
+    /// Header to emit. Its `size` is recomputed from [`BoxWrite::body_size`] on write.
+    fn header(&self) -> BoxHeader;
+    fn body_size(&self) -> u64;
+    async fn write_body<W: AsyncWrite + Unpin + Send>(&self, writer: &mut W) -> io::Result<()>;
+}
+
+#[async_trait::async_trait]
+impl<T: BoxWrite> AttrWrite for T {
+    fn attr_size(&self) -> u64 {
+        self.header().resized(self.body_size()).size
+    }
+
+    async fn write_attr<W: AsyncWrite + Unpin + Send>(&self, writer: &mut W) -> io::Result<()> {
+        let header = self.header().resized(self.body_size());
+        header.write(writer).await?;
+        self.write_body(writer).await
+    }
+}
+
+/// Child boxes held by a field of an internal box, written one at a time to
+/// interleave them with the other fields' boxes in their original order
+#[async_trait::async_trait]
+pub trait ChildWrite: Sync {
+    fn child_count(&self) -> usize;
+    async fn write_child<W: AsyncWrite + Unpin + Send>(
+        &self,
+        index: usize,
+        writer: &mut W,
+    ) -> io::Result<()>;
+
+    /// Writes the children from `index` on
+    async fn write_children_from<W: AsyncWrite + Unpin + Send>(
+        &self,
+        index: usize,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        for index in index..self.child_count() {
+            self.write_child(index, writer).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: BoxWrite> ChildWrite for T {
+    fn child_count(&self) -> usize {
+        1
+    }
+
+    async fn write_child<W: AsyncWrite + Unpin + Send>(
+        &self,
+        _: usize,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        self.write_attr(writer).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: BoxWrite> ChildWrite for Option<T> {
+    fn child_count(&self) -> usize {
+        self.is_some() as usize
+    }
+
+    async fn write_child<W: AsyncWrite + Unpin + Send>(
+        &self,
+        index: usize,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        if let Some(inner) = self {
+            inner.write_child(index, writer).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: BoxWrite> ChildWrite for Vec<T> {
+    fn child_count(&self) -> usize {
+        self.len()
+    }
+
+    async fn write_child<W: AsyncWrite + Unpin + Send>(
+        &self,
+        index: usize,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        self[index].write_attr(writer).await
+    }
+}
+
+#[async_trait::async_trait]
+impl BoxWrite for RawBox {
+    fn header(&self) -> BoxHeader {
+        self.header
+    }
+
+    fn body_size(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    async fn write_body<W: AsyncWrite + Unpin + Send>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.data).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: Sync> AttrWrite for PhantomData<T> {
+    fn attr_size(&self) -> u64 {
+        0
+    }
+
+    async fn write_attr<W: AsyncWrite + Unpin + Send>(&self, _: &mut W) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<const N: usize> AttrWrite for [u8; N] {
+    fn attr_size(&self) -> u64 {
+        N as u64
+    }
+
+    async fn write_attr<W: AsyncWrite + Unpin + Send>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(self).await
+    }
+}
+
+#[async_trait::async_trait]
+impl AttrWrite for u8 {
+    fn attr_size(&self) -> u64 {
+        1
+    }
+
+    async fn write_attr<W: AsyncWrite + Unpin + Send>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_u8(*self).await
+    }
+}
+
+#[async_trait::async_trait]
+impl AttrWrite for u16 {
+    fn attr_size(&self) -> u64 {
+        2
+    }
+
+    async fn write_attr<W: AsyncWrite + Unpin + Send>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_u16(*self).await
+    }
+}
+
+#[async_trait::async_trait]
+impl AttrWrite for u32 {
+    fn attr_size(&self) -> u64 {
+        4
+    }
+
+    async fn write_attr<W: AsyncWrite + Unpin + Send>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_u32(*self).await
+    }
+}
+
+#[async_trait::async_trait]
+impl AttrWrite for u64 {
+    fn attr_size(&self) -> u64 {
+        8
+    }
+
+    async fn write_attr<W: AsyncWrite + Unpin + Send>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_u64(*self).await
+    }
+}
+
+#[async_trait::async_trait]
+impl AttrWrite for U32Tag {
+    fn attr_size(&self) -> u64 {
+        4
+    }
+
+    async fn write_attr<W: AsyncWrite + Unpin + Send>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.raw).await
+    }
+}
+
+#[async_trait::async_trait]
+impl AttrWrite for RawString {
+    fn attr_size(&self) -> u64 {
+        self.raw.len() as u64
+    }
+
+    async fn write_attr<W: AsyncWrite + Unpin + Send>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.raw).await
+    }
+}
+
+#[async_trait::async_trait]
+impl AttrWrite for Bytes {
+    fn attr_size(&self) -> u64 {
+        self.len() as u64
+    }
+
+    async fn write_attr<W: AsyncWrite + Unpin + Send>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(self).await
+    }
+}
+
+// async_trait names the elided lifetime of the reference, which clippy then asks to elide
+#[allow(clippy::needless_lifetimes)]
+#[async_trait::async_trait]
+impl AttrWrite for &[u8] {
+    fn attr_size(&self) -> u64 {
+        self.len() as u64
+    }
+
+    async fn write_attr<W: AsyncWrite + Unpin + Send>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(self).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: AttrWrite> AttrWrite for Vec<T> {
+    fn attr_size(&self) -> u64 {
+        self.iter().map(AttrWrite::attr_size).sum()
+    }
+
+    async fn write_attr<W: AsyncWrite + Unpin + Send>(&self, writer: &mut W) -> io::Result<()> {
+        for elem in self {
+            elem.write_attr(writer).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: AttrWrite> AttrWrite for Option<T> {
+    fn attr_size(&self) -> u64 {
+        self.as_ref().map(AttrWrite::attr_size).unwrap_or(0)
+    }
+
+    async fn write_attr<W: AsyncWrite + Unpin + Send>(&self, writer: &mut W) -> io::Result<()> {
+        if let Some(inner) = self {
+            inner.write_attr(writer).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Free {
+        header: BoxHeader,
+        data: Vec<u8>,
+    }
+
+    #[async_trait::async_trait]
+    impl BoxWrite for Free {
+        fn header(&self) -> BoxHeader {
+            self.header
+        }
+
+        fn body_size(&self) -> u64 {
+            self.data.attr_size()
+        }
+
+        async fn write_body<W: AsyncWrite + Unpin + Send>(&self, writer: &mut W) -> io::Result<()> {
+            self.data.write_attr(writer).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_size() {
+        let free = Free {
+            header: BoxHeader {
+                id: *b"free",
+                size: 0,
+                largesize: false,
+                usertype: None,
+            },
+            data: vec![1, 2, 3],
+        };
+        let mut buf = Vec::new();
+        free.write_attr(&mut buf).await.unwrap();
+        assert_eq!(buf, [0, 0, 0, 11, b'f', b'r', b'e', b'e', 1, 2, 3]);
+
+        let free = Free {
+            header: BoxHeader {
+                largesize: true,
+                ..free.header
+            },
+            ..free
+        };
+        let mut buf = Vec::new();
+        free.write_attr(&mut buf).await.unwrap();
+        assert_eq!(free.attr_size(), 19);
+        assert_eq!(
+            buf,
+            [0, 0, 0, 1, b'f', b'r', b'e', b'e', 0, 0, 0, 0, 0, 0, 0, 19, 1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn test_resized_to_largesize() {
+        let header = BoxHeader {
+            id: *b"mdat",
+            size: 0,
+            largesize: false,
+            usertype: None,
+        };
+        let resized = header.resized(u32::MAX as u64);
+        assert!(resized.largesize);
+        assert_eq!(resized.size, u32::MAX as u64 + 16);
+        assert_eq!(resized.body_size(), u32::MAX as usize);
+    }
+}
@@ -1,5 +1,5 @@
 use movparse_box::*;
-use tokio::io::{self, AsyncRead, AsyncSeek};
+use tokio::io::{AsyncRead, AsyncSeek};
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Ftyp {
@@ -17,7 +17,7 @@ impl movparse_box::BoxRead for Ftyp {
     async fn read_body<R: AsyncRead + AsyncSeek + Unpin + Send>(
         header: BoxHeader,
         reader: &mut Reader<R>,
-    ) -> io::Result<Self> {
+    ) -> Result<Self, Error> {
         let mut reader2 = reader.clone();
         reader2.set_limit(header.body_size() as u64);
         let major_brand = AttrRead::read_attr(&mut reader2).await?;
@@ -46,7 +46,7 @@ impl movparse_box::BoxRead for Data {
     async fn read_body<R: AsyncRead + AsyncSeek + Unpin + Send>(
         header: BoxHeader,
         reader: &mut Reader<R>,
-    ) -> io::Result<Self> {
+    ) -> Result<Self, Error> {
         reader.seek_from_current(header.body_size() as i64).await?;
         Ok(Self { header })
     }
@@ -67,7 +67,7 @@ impl movparse_box::BoxRead for Test {
     async fn read_body<R: AsyncRead + AsyncSeek + Unpin + Send>(
         header: BoxHeader,
         reader: &mut Reader<R>,
-    ) -> io::Result<Self> {
+    ) -> Result<Self, Error> {
         let mut reader2 = reader.clone();
         reader2.set_limit(header.body_size() as u64);
         let mut ftyp = Ftyp::placeholder();
@@ -75,18 +75,16 @@ impl movparse_box::BoxRead for Test {
         while reader2.remain() > 0 {
             let header = BoxHeader::read(&mut reader2).await?;
             if ftyp.acceptable_tag(header.id) {
-                let ftyp_value = ftyp.read_body(header, &mut reader2).await?;
-                BoxPlaceholder::push(&mut ftyp, ftyp_value)?;
+                ftyp.read_child(header, &mut reader2).await?;
             } else if data.acceptable_tag(header.id) {
-                let data_value = data.read_body(header, &mut reader2).await?;
-                BoxPlaceholder::push(&mut data, data_value)?;
+                data.read_child(header, &mut reader2).await?;
             }
         }
         reader.seek_from_current(header.body_size() as i64).await?;
         Ok(Self {
             header,
-            ftyp: ftyp.get("ftyp")?,
-            data: data.get("data")?,
+            ftyp: ftyp.get("ftyp").map_err(|kind| reader.error(kind))?,
+            data: data.get("data").map_err(|kind| reader.error(kind))?,
         })
     }
 }
@@ -100,6 +98,7 @@ mod test {
     use super::*;
 
     #[tokio::test]
+    #[allow(clippy::byte_char_slices)]
     async fn test() {
         let mut ftyp = Vec::new();
         let major_brand = &[b'r', b'u', b's', b't'];
@@ -140,7 +139,9 @@ mod test {
             Ftyp {
                 header: BoxHeader {
                     id: [b'f', b't', b'y', b'p'],
-                    size: 24
+                    size: 24,
+                    largesize: false,
+                    usertype: None,
                 },
                 major_brand: [b'r', b'u', b's', b't'],
                 minor_version: [b'm', b'p', b'4', b'r'],
@@ -176,20 +177,26 @@ mod test {
             Test {
                 header: BoxHeader {
                     id: [b't', b'e', b's', b't'],
-                    size: 108 + 108 + 24 + 8
+                    size: 108 + 108 + 24 + 8,
+                    largesize: false,
+                    usertype: None,
                 },
                 ftyp: ftyp_body,
                 data: vec![
                     Data {
                         header: BoxHeader {
                             id: [b'd', b'a', b't', b'a'],
-                            size: 108
+                            size: 108,
+                            largesize: false,
+                            usertype: None,
                         },
                     },
                     Data {
                         header: BoxHeader {
                             id: [b'd', b'a', b't', b'a'],
-                            size: 108
+                            size: 108,
+                            largesize: false,
+                            usertype: None,
                         },
                     }
                 ]
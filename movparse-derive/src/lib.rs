@@ -1,86 +1,1033 @@
 use itertools::{Either, Itertools};
 use proc_macro::TokenStream;
 use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
-use quote::{quote, ToTokens, TokenStreamExt};
+use quote::{quote, quote_spanned, ToTokens, TokenStreamExt};
 use syn::{
-    parse_macro_input,
-    punctuated::Punctuated,
-    spanned::Spanned,
-    token::{Bracket, Colon2},
-    Attribute, DeriveInput, Expr, ExprAssign, ExprPath, Fields, LitStr, Type,
+    parse_macro_input, parse_quote, punctuated::Punctuated, spanned::Spanned, token::Colon2,
+    Attribute, DeriveInput, Expr, ExprAssign, ExprPath, Fields, Generics, LitStr, Type,
 };
 
-fn acceptable_tag(attrs: &Vec<Attribute>, span: &Span) -> Result<syn::Expr, syn::Error> {
-    let attrs = parse_mp4_attrs(attrs)?;
-    let mut tags = Punctuated::new();
-    for attr in attrs {
+/// Tokens that differ between the async traits and their `movparse_box::blocking` and `movparse_box::slice` counterparts
+struct Flavor {
+    module: TokenStream2,
+    /// Lifetime of the buffer the `movparse_box::slice` traits read, which the derived types may borrow
+    source_lifetime: Option<syn::Lifetime>,
+    await_: TokenStream2,
+    impl_attr: TokenStream2,
+    read_body_sig: TokenStream2,
+    root_read_sig: TokenStream2,
+    read_attr_sig: TokenStream2,
+    read_header: TokenStream2,
+    /// Bounds on the type parameters of the derived impls
+    type_bounds: TokenStream2,
+    scan_top_level: TokenStream2,
+    /// Function of `#[mp4(with)]` modules reading a field
+    with_read: TokenStream2,
+    /// Trait giving the box types of child fields to the checks of the readers
+    child_tags: TokenStream2,
+    /// Read only to instrument the generated parsers
+    #[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+    is_async: bool,
+}
+
+impl Flavor {
+    fn all() -> [Flavor; 2] {
+        [
+            Flavor {
+                module: quote! {::movparse_box},
+                source_lifetime: None,
+                await_: quote! {.await},
+                impl_attr: quote! {#[::async_trait::async_trait]},
+                read_body_sig: quote! {
+                    async fn read_body<R: ::tokio::io::AsyncRead + ::tokio::io::AsyncSeek + ::std::marker::Unpin + ::std::marker::Send>(
+                        header: ::movparse_box::BoxHeader,
+                        reader: &mut ::movparse_box::Reader<R>,
+                    ) -> ::std::result::Result<Self, ::movparse_box::Error>
+                },
+                root_read_sig: quote! {
+                    async fn read<R: ::tokio::io::AsyncRead + ::tokio::io::AsyncSeek + ::std::marker::Unpin + ::std::marker::Send>(
+                        reader: &mut ::movparse_box::Reader<R>,
+                    ) -> ::std::result::Result<Self, ::movparse_box::Error>
+                },
+                read_attr_sig: quote! {
+                    async fn read_attr<R: ::tokio::io::AsyncRead + ::tokio::io::AsyncSeek + ::std::marker::Unpin + ::std::marker::Send>(
+                        reader: &mut ::movparse_box::Reader<R>,
+                    ) -> ::std::result::Result<Self, ::movparse_box::Error>
+                },
+                read_header: quote! {::movparse_box::BoxHeader::read(&mut reader2).await},
+                type_bounds: send_sync(),
+                scan_top_level: quote! {::movparse_box::TopLevel::scan(reader).await},
+                with_read: quote! {read},
+                child_tags: quote! {::movparse_box::ChildTags},
+                is_async: true,
+            },
+            Flavor {
+                module: quote! {::movparse_box::blocking},
+                source_lifetime: None,
+                await_: TokenStream2::new(),
+                impl_attr: TokenStream2::new(),
+                read_body_sig: quote! {
+                    fn read_body<R: ::movparse_box::blocking::Source>(
+                        header: ::movparse_box::BoxHeader,
+                        reader: &mut ::movparse_box::blocking::Reader<R>,
+                    ) -> ::std::result::Result<Self, ::movparse_box::Error>
+                },
+                root_read_sig: quote! {
+                    fn read<R: ::movparse_box::blocking::Source>(
+                        reader: &mut ::movparse_box::blocking::Reader<R>,
+                    ) -> ::std::result::Result<Self, ::movparse_box::Error>
+                },
+                read_attr_sig: quote! {
+                    fn read_attr<R: ::movparse_box::blocking::Source>(
+                        reader: &mut ::movparse_box::blocking::Reader<R>,
+                    ) -> ::std::result::Result<Self, ::movparse_box::Error>
+                },
+                read_header: quote! {::movparse_box::BoxHeader::read_blocking(&mut reader2)},
+                type_bounds: TokenStream2::new(),
+                scan_top_level: quote! {::movparse_box::TopLevel::scan_blocking(reader)},
+                with_read: quote! {read_blocking},
+                child_tags: quote! {::movparse_box::ChildTags},
+                is_async: false,
+            },
+        ]
+    }
+
+    /// The `movparse_box::slice` traits, whose readers hand out slices of the buffer for `'__src`
+    fn slice() -> Flavor {
+        Flavor {
+            module: quote! {::movparse_box::slice},
+            source_lifetime: Some(parse_quote! {'__src}),
+            await_: TokenStream2::new(),
+            impl_attr: TokenStream2::new(),
+            read_body_sig: quote! {
+                fn read_body(
+                    header: ::movparse_box::BoxHeader,
+                    reader: &mut ::movparse_box::slice::Reader<'__src>,
+                ) -> ::std::result::Result<Self, ::movparse_box::Error>
+            },
+            root_read_sig: quote! {
+                fn read(
+                    reader: &mut ::movparse_box::slice::Reader<'__src>,
+                ) -> ::std::result::Result<Self, ::movparse_box::Error>
+            },
+            read_attr_sig: quote! {
+                fn read_attr(
+                    reader: &mut ::movparse_box::slice::Reader<'__src>,
+                ) -> ::std::result::Result<Self, ::movparse_box::Error>
+            },
+            read_header: quote! {::movparse_box::BoxHeader::read_blocking(&mut reader2)},
+            type_bounds: TokenStream2::new(),
+            scan_top_level: quote! {::movparse_box::TopLevel::scan_blocking(reader)},
+            with_read: quote! {read_blocking},
+            child_tags: quote! {::movparse_box::slice::ChildTags},
+            is_async: false,
+        }
+    }
+
+    /// Flavors derived for a type: only the `movparse_box::slice` one for types marked `#[mp4(borrow)]`,
+    /// whose fields borrow the buffer, the async and blocking ones otherwise
+    fn of(attrs: &Vec<Attribute>) -> Result<Vec<Flavor>, syn::Error> {
+        Ok(if borrows(attrs)? {
+            vec![Flavor::slice()]
+        } else {
+            Flavor::all().into()
+        })
+    }
+
+    /// Arguments of the derived traits, the lifetime of the buffer for the `movparse_box::slice` ones
+    fn trait_args(&self) -> TokenStream2 {
+        match &self.source_lifetime {
+            Some(lifetime) => quote! {<#lifetime>},
+            None => TokenStream2::new(),
+        }
+    }
+
+    /// [`split_generics`] with the bounds of the flavor. The lifetime of the buffer is added and outlives every
+    /// lifetime of the type, so its fields can borrow the buffer.
+    fn split_generics(&self, name: &Ident, generics: &Generics) -> (TokenStream2, TokenStream2) {
+        let Some(source_lifetime) = &self.source_lifetime else {
+            return split_generics(name, generics, &self.type_bounds);
+        };
+        let (_, self_ty) = split_generics(name, generics, &self.type_bounds);
+        let mut generics = generics.clone();
+        let mut param = syn::LifetimeDef::new(source_lifetime.clone());
+        param
+            .bounds
+            .extend(generics.lifetimes().map(|param| param.lifetime.clone()));
+        generics
+            .params
+            .insert(0, syn::GenericParam::Lifetime(param));
+        let (impl_generics, _, _) = generics.split_for_impl();
+        (quote! {#impl_generics}, self_ty)
+    }
+
+    /// Body of `read_body` running `inner` in a span of the box,
+    /// which records the bytes read and the time taken once it returns
+    #[cfg(feature = "tracing")]
+    fn instrument(&self, name: &Ident, inner: TokenStream2) -> TokenStream2 {
+        let run = if self.is_async {
+            quote! {
+                ::movparse_box::tracing::Instrument::instrument(async { #inner }, span.clone()).await
+            }
+        } else {
+            quote! {span.in_scope(|| { #inner })}
+        };
+        let ty = name.to_string();
+        quote! {
+            let span = ::movparse_box::tracing::debug_span!(
+                "box",
+                fourcc = %header.id.escape_ascii(),
+                ty = #ty,
+                offset = reader.pos - header.header_size(),
+                size = header.size,
+                path = %reader.path,
+                bytes_read = ::movparse_box::tracing::field::Empty,
+                elapsed_us = ::movparse_box::tracing::field::Empty,
+            );
+            let start = ::std::time::Instant::now();
+            let start_pos = reader.pos;
+            let result: ::std::result::Result<Self, ::movparse_box::Error> = #run;
+            span.record("bytes_read", reader.pos - start_pos);
+            span.record("elapsed_us", start.elapsed().as_micros() as u64);
+            match &result {
+                Ok(_) => ::movparse_box::tracing::debug!(parent: &span, "read box"),
+                Err(e) => ::movparse_box::tracing::debug!(parent: &span, error = %e, "failed to read box"),
+            }
+            result
+        }
+    }
+
+    #[cfg(not(feature = "tracing"))]
+    fn instrument(&self, _: &Ident, inner: TokenStream2) -> TokenStream2 {
+        inner
+    }
+}
+
+/// Bounds of the async traits, whose futures hold fields across awaits and must be `Send`
+fn send_sync() -> TokenStream2 {
+    quote! {::std::marker::Send + ::std::marker::Sync}
+}
+
+/// `impl<...>` and `for Name<...> where ...` of a derived impl, with `bounds` added to every type parameter
+fn split_generics(
+    name: &Ident,
+    generics: &Generics,
+    bounds: &TokenStream2,
+) -> (TokenStream2, TokenStream2) {
+    let mut generics = generics.clone();
+    if !bounds.is_empty() {
+        let params = generics
+            .type_params()
+            .map(|param| param.ident.clone())
+            .collect_vec();
+        let where_clause = generics.make_where_clause();
+        for param in params {
+            where_clause.predicates.push(parse_quote! {#param: #bounds});
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    (
+        quote! {#impl_generics},
+        quote! {#name #ty_generics #where_clause},
+    )
+}
+
+/// Box types accepted by a struct or variant, from `#[mp4(tag = "...")]` and `#[mp4(uuid = "...")]`
+struct BoxTypes {
+    tags: Vec<[u8; 4]>,
+    uuids: Vec<[u8; 16]>,
+}
+
+impl BoxTypes {
+    fn parse(attrs: &Vec<Attribute>, span: &Span) -> Result<Self, syn::Error> {
+        let attrs = parse_mp4_attrs(attrs)?;
+        let mut tags = Vec::new();
+        let mut uuids = Vec::new();
+        for attr in attrs {
+            if let Mp4Attr::WithValue(path, value) = attr {
+                if path.is_ident("tag") {
+                    tags.push(parse_fourcc(
+                        &value,
+                        "mp4(tag = <4 char str, 4 byte str or u32>)",
+                    )?);
+                } else if path.is_ident("uuid") {
+                    let uuid = match &value {
+                        syn::Lit::Str(s) => parse_uuid(&s.value()),
+                        _ => None,
+                    };
+                    let uuid = uuid.ok_or_else(|| {
+                        syn::Error::new_spanned(
+                            value.to_token_stream(),
+                            "mp4(uuid = <\"xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx\">)",
+                        )
+                    })?;
+                    uuids.push(uuid);
+                }
+            }
+        }
+
+        if tags.is_empty() && uuids.is_empty() {
+            return Err(syn::Error::new(
+                *span,
+                "at least 1 mp4(tag = <4 char str, 4 byte str or u32>) or mp4(uuid = <str>) required",
+            ));
+        }
+        Ok(Self { tags, uuids })
+    }
+
+    fn merge(types: impl IntoIterator<Item = Self>) -> Self {
+        let mut merged = Self {
+            tags: Vec::new(),
+            uuids: Vec::new(),
+        };
+        for types in types {
+            merged.tags.extend(types.tags);
+            merged.uuids.extend(types.uuids);
+        }
+        merged
+    }
+
+    /// Expression testing the `[u8; 4]` expression `tag`
+    fn accepts_tag(&self, tag: TokenStream2) -> TokenStream2 {
+        let mut tags = self.tags.clone();
+        if !self.uuids.is_empty() {
+            tags.push(*b"uuid");
+        }
+        let tags = tags.iter().map(|tag| quote! {[#(#tag),*]});
+        quote! {[#(#tags),*].contains(&#tag)}
+    }
+
+    /// Expression testing the `BoxHeader` expression `header`
+    fn accepts_header(&self, header: TokenStream2) -> TokenStream2 {
+        let by_tag = if self.tags.is_empty() {
+            quote! {false}
+        } else {
+            let tags = self.tags.iter().map(|tag| quote! {[#(#tag),*]});
+            quote! {[#(#tags),*].contains(&#header.id)}
+        };
+        if self.uuids.is_empty() {
+            return by_tag;
+        }
+        let uuids = self.uuids.iter().map(|uuid| quote! {[#(#uuid),*]});
+        quote! {
+            (#by_tag || (
+                #header.id == ::movparse_box::BoxHeader::UUID
+                    && #header.usertype.is_some_and(|usertype| [#(#uuids),*].contains(&usertype))
+            ))
+        }
+    }
+
+    /// `TAGS`, `acceptable_tag` and `acceptable_header` of `BoxRead`
+    fn gen_acceptable(&self) -> TokenStream2 {
+        let accepts_tag = self.accepts_tag(quote! {tag});
+        let accepts_header = self.accepts_header(quote! {header});
+        // `uuid` boxes share their tag and are told apart by the usertype
+        let tags = self.tags.iter().map(|tag| quote! {[#(#tag),*]});
+        quote! {
+            const TAGS: ::std::option::Option<&'static [[u8; 4]]> = ::std::option::Option::Some(&[#(#tags),*]);
+
+            fn acceptable_tag(tag: [u8;4]) -> bool {
+                #accepts_tag
+            }
+
+            fn acceptable_header(header: &::movparse_box::BoxHeader) -> bool {
+                #accepts_header
+            }
+        }
+    }
+}
+
+/// Four character code written as a string, byte string or integer literal.
+/// Strings of four characters up to U+00FF are read as Latin-1, so `"©nam"` is `b"\xA9nam"`.
+fn parse_fourcc(value: &syn::Lit, usage: &str) -> Result<[u8; 4], syn::Error> {
+    let fourcc = match value {
+        syn::Lit::Str(s) => {
+            let s = s.value();
+            let latin1 = s
+                .chars()
+                .map(|c| u8::try_from(c).ok())
+                .collect::<Option<Vec<_>>>();
+            match (s.as_bytes(), latin1.as_deref()) {
+                ([a, b, c, d], _) | (_, Some([a, b, c, d])) => Some([*a, *b, *c, *d]),
+                _ => None,
+            }
+        }
+        syn::Lit::ByteStr(s) => s.value().try_into().ok(),
+        syn::Lit::Int(i) => i.base10_parse::<u32>().ok().map(u32::to_be_bytes),
+        _ => None,
+    };
+    fourcc.ok_or_else(|| syn::Error::new_spanned(value.to_token_stream(), usage))
+}
+
+/// Parses the hyphenated (or plain) hex form of a UUID
+fn parse_uuid(s: &str) -> Option<[u8; 16]> {
+    let digits = s.chars().filter(|c| *c != '-').collect::<Vec<_>>();
+    if digits.len() != 32 {
+        return None;
+    }
+    let mut uuid = [0u8; 16];
+    for (byte, pair) in uuid.iter_mut().zip(digits.chunks(2)) {
+        *byte = u8::from_str_radix(&pair.iter().collect::<String>(), 16).ok()?;
+    }
+    Some(uuid)
+}
+
+/// Versions accepted by `#[mp4(versions = "0,1")]`, `None` if any version is accepted
+fn read_versions(attrs: &Vec<Attribute>) -> Result<Option<Vec<u8>>, syn::Error> {
+    for attr in parse_mp4_attrs(attrs)? {
         if let Mp4Attr::WithValue(path, value) = attr {
-            if path.is_ident("tag") {
-                let syn::Lit::Str(s) = &value else {
-                return Err(syn::Error::new_spanned(value.to_token_stream(), "mp4(tag = <4 char str>)"));
-            };
-                let tag_str = s.value();
-                let [a, b, c, d] = tag_str.as_bytes() else {
-                return Err(syn::Error::new_spanned(value.to_token_stream(), "mp4(tag = <4 char str>)"));
-            };
-                let mut tag_chars = Punctuated::new();
-                for ch in [*a, *b, *c, *d] {
-                    let ch = syn::Lit::Int(syn::LitInt::new(&format!("{}u8", ch), s.span()));
-                    tag_chars.push(syn::Expr::Lit(syn::ExprLit {
-                        attrs: Default::default(),
-                        lit: ch,
-                    }));
-                }
-                let tag = syn::Expr::Array(syn::ExprArray {
-                    attrs: Default::default(),
-                    bracket_token: Bracket::default(),
-                    elems: tag_chars,
+            if path.is_ident("versions") {
+                let versions = match &value {
+                    syn::Lit::Str(s) => s
+                        .value()
+                        .split(',')
+                        .map(|version| version.trim().parse::<u8>().ok())
+                        .collect::<Option<Vec<_>>>(),
+                    _ => None,
+                };
+                return versions.map(Some).ok_or_else(|| {
+                    syn::Error::new_spanned(
+                        value.to_token_stream(),
+                        "mp4(versions = <comma separated u8 list>)",
+                    )
                 });
-                tags.push(tag);
             }
         }
     }
+    Ok(None)
+}
 
-    if tags.is_empty() {
-        return Err(syn::Error::new(
-            *span,
-            "at least 1 mp4(tag = <4 char str>) required",
-        ));
+/// Check of `#[mp4(validate = "path")]` on the box read into `value`, after the reader is moved past the box.
+/// The function takes the box (for enum variants, the whole enum) and returns `Result<(), ErrorKind>`.
+fn gen_validate(attrs: &Vec<Attribute>) -> Result<TokenStream2, syn::Error> {
+    for attr in parse_mp4_attrs(attrs)? {
+        if let Mp4Attr::WithValue(path, value) = attr {
+            if path.is_ident("validate") {
+                let validate = match &value {
+                    syn::Lit::Str(s) => s.parse::<ExprPath>().ok(),
+                    _ => None,
+                };
+                let validate = validate.ok_or_else(|| {
+                    syn::Error::new_spanned(value.to_token_stream(), "mp4(validate = <fn path>)")
+                })?;
+                return Ok(quote! {
+                    if let Err(kind) = #validate(&value) {
+                        return Err(::movparse_box::Error::new(
+                            kind,
+                            reader.path.clone(),
+                            reader.pos - header.body_size() as u64,
+                        ));
+                    }
+                });
+            }
+        }
     }
+    Ok(TokenStream2::new())
+}
 
-    let tags_def = syn::Expr::Array(syn::ExprArray {
-        attrs: Vec::new(),
-        bracket_token: Bracket::default(),
-        elems: tags,
+/// Reads a normal field from `reader2: &mut Reader`. A `#[mp4(fullbox)]` field also binds `version` and `flags`
+/// for the fields after it and is checked against `versions`.
+fn gen_read_leaf_field(
+    allocated_name: &Ident,
+    ty: &Type,
+    field_name: &str,
+    options: &FieldOptions,
+    placement: &FieldPlacement,
+    versions: &Option<Vec<u8>>,
+    flavor: &Flavor,
+) -> TokenStream2 {
+    let Flavor {
+        module,
+        await_,
+        with_read,
+        ..
+    } = flavor;
+    let FieldPlacement {
+        bits,
+        optional,
+        last,
+    } = placement;
+    let padding = match (options.skip, bits) {
+        (Some(skip), _) => quote! {
+            let _: [u8; #skip] = #module::AttrRead::read_attr(reader2)#await_?;
+        },
+        (None, Some(BitsRole::Start { group, bytes, .. })) => quote! {
+            let #group: [u8; #bytes] = #module::AttrRead::read_attr(reader2)#await_?;
+            let mut #group = ::movparse_box::BitReader::new(&#group);
+        },
+        _ => TokenStream2::new(),
+    };
+    if let Some(default) = &options.default {
+        return quote! {
+            #padding
+            let #allocated_name: #ty = #default;
+        };
+    }
+    let read = match (&options.count, bits) {
+        (_, Some(role)) => {
+            let group = role.group();
+            let width = options.bits.unwrap_or_default();
+            let message = format!(
+                "the type of `{}` holds fewer than {} bits",
+                field_name, width
+            );
+            quote! {{
+                const { assert!(#width <= <#ty as ::movparse_box::Bits>::BITS, #message) };
+                ::movparse_box::Bits::from_bits(#group.read(#width).expect("the run is read as a whole"))
+            }}
+        }
+        // exactly `count` elements, running out of bytes first is an error instead of the end of the table
+        (Some(count), None) => {
+            // bytes left after the last field would be dropped silently
+            let check_rest = last.then(|| {
+                quote! {
+                    if reader2.remain() > 0 {
+                        return Err(reader2.error(::movparse_box::ErrorKind::TrailingBytes {
+                            field: #field_name.to_owned(),
+                            count,
+                            remaining: reader2.remain() as u64,
+                        }));
+                    }
+                }
+            });
+            quote! {{
+                let count = (#count).clone() as u64;
+                let mut entries = ::std::vec::Vec::new();
+                while (entries.len() as u64) < count {
+                    match #module::AttrRead::read_attr(reader2)#await_ {
+                        Ok(entry) => entries.push(entry),
+                        Err(::movparse_box::Error {
+                            kind: ::movparse_box::ErrorKind::Truncated { .. },
+                            ..
+                        }) => {
+                            return Err(reader2.error(::movparse_box::ErrorKind::CountMismatch {
+                                field: #field_name.to_owned(),
+                                count,
+                                read: entries.len() as u64,
+                            }));
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+                #check_rest
+                entries
+            }}
+        }
+        (None, None) => match &options.with {
+            Some(with) => quote! {#with::#with_read(reader2)#await_?},
+            None => quote! {#module::AttrRead::read_attr(reader2)#await_?},
+        },
+    };
+    if options.fullbox {
+        let check = versions.as_ref().map(|versions| {
+            quote! {
+                if ![#(#versions),*].contains(&version) {
+                    return Err(::movparse_box::Error::new(
+                        ::movparse_box::ErrorKind::UnsupportedVersion { version },
+                        reader2.path.clone(),
+                        reader2.pos - 4,
+                    ));
+                }
+            }
+        });
+        return quote! {
+            #padding
+            let #allocated_name: #ty = #read;
+            #[allow(unused_variables)]
+            let (version, flags) = (#allocated_name.version, #allocated_name.flags);
+            #check
+        };
+    }
+    let read = match &options.cond {
+        Some(Cond {
+            expr, option: true, ..
+        }) => quote! {
+            let #allocated_name: #ty = if #expr { Some(#read) } else { None };
+        },
+        Some(Cond { expr, .. }) => quote! {
+            let #allocated_name: #ty = if #expr {
+                ::movparse_box::Either::Left(#read)
+            } else {
+                ::movparse_box::Either::Right(#read)
+            };
+        },
+        // trailing fields older writers omit, absent when the rest of the body is too short for them
+        None if *optional => quote! {
+            let #allocated_name: #ty = if reader2.remain() <= 0 {
+                None
+            } else {
+                let mut probe = reader2.clone();
+                match #module::AttrRead::read_attr(&mut probe)#await_ {
+                    Ok(value) => {
+                        *reader2 = probe;
+                        Some(value)
+                    }
+                    Err(::movparse_box::Error {
+                        kind: ::movparse_box::ErrorKind::Truncated { .. },
+                        ..
+                    }) => None,
+                    Err(e) => return Err(e),
+                }
+            };
+        },
+        None => quote! {
+            let #allocated_name: #ty = #read;
+        },
+    };
+    let discard = options.reserved.then(|| {
+        quote! {
+            let _ = #allocated_name;
+            let #allocated_name: #ty = ::std::default::Default::default();
+        }
+    });
+    if options.magic.is_none() && options.asserts.is_empty() {
+        return quote! {
+            #padding
+            #read
+            #discard
+        };
+    }
+    let offset = offset_name(allocated_name);
+    // bit fields report the start of their run, which is read as a whole before them
+    let offset_value = match bits {
+        Some(role) => {
+            let bytes = role.bytes();
+            quote! {reader2.pos - #bytes as u64}
+        }
+        None => quote! {reader2.pos},
+    };
+    let check_magic = options.magic.as_ref().map(|magic| {
+        quote! {
+            if #allocated_name != #magic {
+            return Err(::movparse_box::Error::new(
+                ::movparse_box::ErrorKind::MagicMismatch {
+                    field: #field_name.to_owned(),
+                    expected: format!("{:?}", #magic),
+                    found: format!("{:?}", #allocated_name),
+                },
+                reader2.path.clone(),
+                #offset,
+            ));
+        }
+        }
     });
+    quote! {
+        #padding
+        let #offset = #offset_value;
+        #read
+        #check_magic
+        #discard
+    }
+}
 
-    Ok(tags_def)
+/// Where a leaf field sits among the other fields, which changes how it is read
+struct FieldPlacement {
+    bits: Option<BitsRole>,
+    /// In the trailing run of `Option` fields, read only when the rest of the body holds them
+    optional: bool,
+    /// No field or child box is read after it, so the body must end with it
+    last: bool,
 }
 
-fn gen_read_leaf_struct_inner(
+/// Place of a field in a run of consecutive `#[mp4(bits)]` fields
+enum BitsRole {
+    /// First field of the run, which reads or writes the bytes of the whole run
+    Start {
+        group: Ident,
+        bytes: usize,
+        members: Vec<(Ident, u32)>,
+    },
+    Member {
+        group: Ident,
+        bytes: usize,
+    },
+}
+
+impl BitsRole {
+    fn group(&self) -> &Ident {
+        match self {
+            Self::Start { group, .. } | Self::Member { group, .. } => group,
+        }
+    }
+
+    fn bytes(&self) -> usize {
+        match self {
+            Self::Start { bytes, .. } | Self::Member { bytes, .. } => *bytes,
+        }
+    }
+}
+
+impl FieldsInfo {
+    /// Bit field roles of the fields other than the header, in order
+    fn bit_roles(&self) -> Result<Vec<Option<BitsRole>>, syn::Error> {
+        let fields = match self {
+            FieldsInfo::Struct { fields, .. } => fields
+                .iter()
+                .map(|field| (&field.allocated_name, &field.options))
+                .collect_vec(),
+            FieldsInfo::Tuple { fields } => fields
+                .iter()
+                .flat_map(|field| match field {
+                    TupleFieldInfo::NormalField {
+                        allocated_name,
+                        options,
+                        ..
+                    } => Some((allocated_name, options.as_ref())),
+                    TupleFieldInfo::Header { .. } => None,
+                })
+                .collect_vec(),
+        };
+        let mut roles = Vec::new();
+        while roles.len() < fields.len() {
+            let (first, _) = fields[roles.len()];
+            let members = fields[roles.len()..]
+                .iter()
+                .map_while(|(name, options)| options.bits.map(|bits| ((*name).clone(), bits)))
+                .collect_vec();
+            let Some((last, _)) = members.last() else {
+                roles.push(None);
+                continue;
+            };
+            let total = members.iter().map(|(_, bits)| bits).sum::<u32>();
+            if total % 8 != 0 {
+                return Err(syn::Error::new(
+                    last.span(),
+                    format!(
+                        "consecutive #[mp4(bits)] fields take {} bits, not whole bytes",
+                        total
+                    ),
+                ));
+            }
+            let group = Ident::new(&format!("{}_bits", first), first.span());
+            let len = members.len();
+            let bytes = total as usize / 8;
+            roles.push(Some(BitsRole::Start {
+                group: group.clone(),
+                bytes,
+                members,
+            }));
+            roles.extend((1..len).map(|_| {
+                Some(BitsRole::Member {
+                    group: group.clone(),
+                    bytes,
+                })
+            }));
+        }
+        Ok(roles)
+    }
+
+    /// Placements of the fields other than the header, in order. `ends_body` tells whether nothing is read after
+    /// the fields, unlike the child boxes of hybrid boxes or the rest of the box around a record.
+    fn placements(&self, ends_body: bool) -> Result<Vec<FieldPlacement>, syn::Error> {
+        let options = self.options();
+        // fields with `#[mp4(default)]` are not read
+        let read_after = options
+            .iter()
+            .rev()
+            .scan(!ends_body, |read_after, options| {
+                let current = *read_after;
+                *read_after |= options.default.is_none();
+                Some(current)
+            })
+            .collect_vec();
+        Ok(self
+            .bit_roles()?
+            .into_iter()
+            .zip(self.optional_tail()?)
+            .zip(read_after.into_iter().rev())
+            .map(|((bits, optional), read_after)| FieldPlacement {
+                bits,
+                optional,
+                last: !read_after,
+            })
+            .collect())
+    }
+
+    /// Options of the fields other than the header, in order
+    fn options(&self) -> Vec<&FieldOptions> {
+        match self {
+            FieldsInfo::Struct { fields, .. } => {
+                fields.iter().map(|field| &field.options).collect_vec()
+            }
+            FieldsInfo::Tuple { fields } => fields
+                .iter()
+                .flat_map(|field| match field {
+                    TupleFieldInfo::NormalField { options, .. } => Some(options.as_ref()),
+                    TupleFieldInfo::Header { .. } => None,
+                })
+                .collect_vec(),
+        }
+    }
+
+    /// Whether each field other than the header belongs to the trailing run of `Option` fields,
+    /// which older writers omit.
+    fn optional_tail(&self) -> Result<Vec<bool>, syn::Error> {
+        let fields = match self {
+            FieldsInfo::Struct { fields, .. } => fields
+                .iter()
+                .map(|field| (&field.decl_ty, &field.options))
+                .collect_vec(),
+            FieldsInfo::Tuple { fields } => fields
+                .iter()
+                .flat_map(|field| match field {
+                    TupleFieldInfo::NormalField {
+                        decl_ty, options, ..
+                    } => Some((decl_ty.as_ref(), options.as_ref())),
+                    TupleFieldInfo::Header { .. } => None,
+                })
+                .collect_vec(),
+        };
+        let optional = |(ty, options): &(&Type, &FieldOptions)| {
+            is_option(ty)
+                && options.cond.is_none()
+                && options.with.is_none()
+                && options.default.is_none()
+        };
+        // the trailer, if any, comes after the run
+        let tail_start = fields
+            .iter()
+            .rposition(|field| !optional(field) && !field.1.trailer)
+            .map_or(0, |idx| idx + 1);
+        if let Some((ty, _)) = fields[..tail_start].iter().find(|field| optional(field)) {
+            return Err(syn::Error::new_spanned(
+                ty,
+                "an Option field without #[mp4(cond)] must be followed only by such fields",
+            ));
+        }
+        Ok((0..fields.len())
+            .map(|idx| idx >= tail_start && !fields[idx].1.trailer)
+            .collect())
+    }
+}
+
+/// Pattern binding a field to write, `_` for fields written without their value
+fn write_binding(allocated_name: &Ident, options: &FieldOptions) -> TokenStream2 {
+    if options.default.is_some() || options.reserved {
+        quote! {_}
+    } else {
+        allocated_name.to_token_stream()
+    }
+}
+
+/// Size expression and write statement of a field bound by reference to `allocated_name`
+fn gen_write_field(
+    allocated_name: &Ident,
+    ty: &Type,
+    options: &FieldOptions,
+    bits: &Option<BitsRole>,
+) -> (TokenStream2, TokenStream2) {
+    if options.default.is_some() {
+        return (quote! {0}, TokenStream2::new());
+    }
+    // the order is bound for the writes of the children, not written itself
+    if options.order {
+        return (
+            quote! {{
+                let _ = #allocated_name;
+                0
+            }},
+            TokenStream2::new(),
+        );
+    }
+    match bits {
+        Some(BitsRole::Start { bytes, members, .. }) => {
+            let (names, widths): (Vec<_>, Vec<_>) = members.iter().cloned().unzip();
+            return (
+                // the size does not depend on the values bound for the writes
+                quote! {{
+                    let _ = (#(#names,)*);
+                    #bytes as u64
+                }},
+                quote! {{
+                    let mut bit_writer = ::movparse_box::BitWriter::new();
+                    #(bit_writer.write(::movparse_box::Bits::to_bits(#names), #widths);)*
+                    ::tokio::io::AsyncWriteExt::write_all(writer, &bit_writer.finish()).await?;
+                }},
+            );
+        }
+        // written by the first field of the run
+        Some(BitsRole::Member { .. }) => return (quote! {0}, TokenStream2::new()),
+        None => (),
+    }
+    let value = if options.reserved {
+        quote! {&<#ty as ::std::default::Default>::default()}
+    } else {
+        allocated_name.to_token_stream()
+    };
+    let (size, write) = match &options.with {
+        Some(with) => (
+            quote! {#with::size(#value)},
+            quote! {#with::write(#value, writer).await?;},
+        ),
+        None => (
+            quote! {::movparse_box::AttrWrite::attr_size(#value)},
+            quote! {::movparse_box::AttrWrite::write_attr(#value, writer).await?;},
+        ),
+    };
+    match options.skip {
+        Some(skip) => (
+            quote! {#skip as u64 + #size},
+            quote! {
+                ::tokio::io::AsyncWriteExt::write_all(writer, &[0u8; #skip]).await?;
+                #write
+            },
+        ),
+        None => (size, write),
+    }
+}
+
+/// Offset where a field with checks starts, bound to a name user fields cannot shadow
+fn offset_name(allocated_name: &Ident) -> Ident {
+    Ident::new(&format!("{}_offset", allocated_name), allocated_name.span())
+}
+
+/// Checks of `#[mp4(assert)]`, run once the field is bound to its name
+fn gen_field_asserts(field: &StructFieldInfo) -> TokenStream2 {
+    let offset = offset_name(&field.allocated_name);
+    let field_name = &field.field_name_str_lit;
+    field
+        .options
+        .asserts
+        .iter()
+        .map(|assertion| {
+            let expr = assertion.parse::<Expr>().unwrap();
+            let assertion = assertion.value();
+            quote! {
+                if !(#expr) {
+                    return Err(::movparse_box::Error::new(
+                        ::movparse_box::ErrorKind::AssertionFailed {
+                            field: #field_name.to_owned(),
+                            assertion: #assertion.to_owned(),
+                        },
+                        reader2.path.clone(),
+                        #offset,
+                    ));
+                }
+            }
+        })
+        .collect()
+}
+
+/// Names used by the generated code, which cannot be bound to earlier fields for conditions and counts
+const RESERVED_NAMES: [&str; 3] = ["header", "reader", "reader2"];
+
+/// Statements reading the fields in order and the expression constructing `name` from them.
+/// The header, if any, is filled with `header`, `child_fills` are appended to struct fills.
+/// `ends_body` tells whether the fields are the last things read from the body.
+fn gen_read_fields(
     name: &TokenStream2,
-    fields: &Fields,
+    attrs: &Vec<Attribute>,
+    fields_info: FieldsInfo,
+    child_fills: TokenStream2,
+    ends_body: bool,
     span: Span,
-) -> Result<TokenStream2, syn::Error> {
-    let fields_info = parse_fields(fields, span)?;
-    let (assign_stmts, struct_return) = match fields_info {
+    flavor: &Flavor,
+) -> Result<(TokenStream2, TokenStream2), syn::Error> {
+    let versions = read_versions(attrs)?;
+    let options = fields_info.options();
+    if options.iter().any(|options| options.children) {
+        return Err(syn::Error::new(
+            span,
+            "#[mp4(children)] is only supported by boxtype = \"hybrid\"",
+        ));
+    }
+    if options.iter().any(|options| options.order) {
+        return Err(syn::Error::new(
+            span,
+            "#[mp4(order)] is only supported for child boxes",
+        ));
+    }
+    if options.iter().any(|options| options.unknown) {
+        return Err(syn::Error::new(
+            span,
+            "#[mp4(unknown)] is only supported for child boxes",
+        ));
+    }
+    let has_fullbox = options.iter().any(|options| options.fullbox);
+    if versions.is_some() && !has_fullbox {
+        return Err(syn::Error::new(
+            span,
+            "mp4(versions) requires a #[mp4(fullbox)] field",
+        ));
+    }
+    if !has_fullbox
+        && options
+            .iter()
+            .any(|options| matches!(&options.cond, Some(cond) if cond.if_version))
+    {
+        return Err(syn::Error::new(
+            span,
+            "mp4(if_version) requires a #[mp4(fullbox)] field",
+        ));
+    }
+    match &fields_info {
+        FieldsInfo::Struct { fields, .. } => {
+            if let Some(field) = fields.iter().find(|field| {
+                !field.options.asserts.is_empty()
+                    && RESERVED_NAMES.contains(&field.field_name.to_string().as_str())
+            }) {
+                return Err(syn::Error::new(
+                    field.field_name.span(),
+                    format!(
+                        "#[mp4(assert)] cannot refer to `{}`, use #[mp4(magic)] or #[mp4(validate)]",
+                        field.field_name
+                    ),
+                ));
+            }
+        }
+        FieldsInfo::Tuple { .. } => {
+            if options.iter().any(|options| !options.asserts.is_empty()) {
+                return Err(syn::Error::new(
+                    span,
+                    "#[mp4(assert)] requires named fields, use #[mp4(magic)] or #[mp4(validate)]",
+                ));
+            }
+        }
+    }
+    let refers_fields = options.iter().any(|options| {
+        options.cond.is_some()
+            || options.count.is_some()
+            || !options.asserts.is_empty()
+            || options.default.is_some()
+    });
+    let placements = fields_info.placements(ends_body)?;
+    let read = match fields_info {
         FieldsInfo::Struct {
             header_name,
             fields,
         } => {
-            let header_name = header_name.ok_or_else(|| {
-                syn::Error::new(span, "At least one #[mp4(header)] attribute required")
-            })?;
-
-            let assign_stmts = fields.iter().map(|field| {
-                let allocated_name = &field.allocated_name;
-                quote!{
-                    let #allocated_name = ::movparse_box::AttrRead::read_attr(&mut reader2).await?;
-                }
-            }).fold(TokenStream2::new(), |mut acc, stmt| {
-                acc.append_all(stmt);
-                acc
-            });
+            let assign_stmts = fields
+                .iter()
+                .zip(&placements)
+                .map(|(field, placement)| {
+                    let allocated_name = &field.allocated_name;
+                    let field_name = &field.field_name;
+                    let read = gen_read_leaf_field(
+                        allocated_name,
+                        &field.decl_ty,
+                        &field_name.to_string(),
+                        &field.options,
+                        placement,
+                        &versions,
+                        flavor,
+                    );
+                    // conditions, counts, assertions and defaults refer to fields by name
+                    if refers_fields && !RESERVED_NAMES.contains(&field_name.to_string().as_str()) {
+                        let asserts = gen_field_asserts(field);
+                        quote! {
+                            #read
+                            #[allow(unused_variables)]
+                            let #field_name = &#allocated_name;
+                            #asserts
+                        }
+                    } else {
+                        read
+                    }
+                })
+                .fold(TokenStream2::new(), |mut acc, stmt| {
+                    acc.append_all(stmt);
+                    acc
+                });
             let struct_fills = fields
                 .iter()
                 .map(|field| {
@@ -90,96 +1037,509 @@ fn gen_read_leaf_struct_inner(
                         #field_name: #allocated_name,
                     }
                 })
-                .fold(quote! {#header_name: header,}, |mut acc, fill| {
-                    acc.append_all(fill);
-                    acc
-                });
-            (assign_stmts, quote! {Ok(#name{#struct_fills})})
+                .fold(
+                    header_name
+                        .map(|header_name| quote! {#header_name: header,})
+                        .unwrap_or_default(),
+                    |mut acc, fill| {
+                        acc.append_all(fill);
+                        acc
+                    },
+                );
+            (assign_stmts, quote! {#name{#struct_fills #child_fills}})
         }
         FieldsInfo::Tuple { fields } => {
-            let assign_stmts = fields.iter().flat_map(|field| {
-                match field {
-                    TupleFieldInfo::Header { allocated_name:_ } => None,
-                    TupleFieldInfo::NormalField { ty: _, allocated_name }  => Some({
-                        quote!{
-                            let #allocated_name = ::movparse_box::AttrRead::read_attr(&mut reader2).await?;
-                        }
-                    })
-                }
-            }).fold(TokenStream2::new(), |mut acc, stmt| {
-                acc.append_all(stmt);
-                acc
-            });
+            let mut placements = placements.iter();
+            let assign_stmts = fields
+                .iter()
+                .enumerate()
+                .flat_map(|(idx, field)| match field {
+                    TupleFieldInfo::Header { allocated_name: _ } => None,
+                    TupleFieldInfo::NormalField {
+                        allocated_name,
+                        decl_ty,
+                        options,
+                        ..
+                    } => Some(gen_read_leaf_field(
+                        allocated_name,
+                        decl_ty,
+                        &format!("${}", idx),
+                        options,
+                        placements.next().unwrap(),
+                        &versions,
+                        flavor,
+                    )),
+                })
+                .fold(TokenStream2::new(), |mut acc, stmt| {
+                    acc.append_all(stmt);
+                    acc
+                });
             let struct_fills = fields
                 .iter()
                 .map(|field| match field {
                     TupleFieldInfo::Header { allocated_name: _ } => quote! {header,},
-                    TupleFieldInfo::NormalField {
-                        ty: _,
-                        allocated_name,
-                    } => quote! {#allocated_name,},
+                    TupleFieldInfo::NormalField { allocated_name, .. } => quote! {#allocated_name,},
                 })
                 .fold(TokenStream2::new(), |mut acc, fill| {
                     acc.append_all(fill);
                     acc
                 });
-            (assign_stmts, quote! {Ok(#name(#struct_fills))})
+            (assign_stmts, quote! {#name(#struct_fills)})
+        }
+    };
+    Ok(read)
+}
+
+fn gen_read_leaf_struct_inner(
+    name: &TokenStream2,
+    _: &Generics,
+    attrs: &Vec<Attribute>,
+    fields: &Fields,
+    span: Span,
+    flavor: &Flavor,
+) -> Result<TokenStream2, syn::Error> {
+    let Flavor { await_, .. } = flavor;
+    let fields_info = parse_fields(fields, span)?;
+    let has_header = match &fields_info {
+        FieldsInfo::Struct { header_name, .. } => header_name.is_some(),
+        FieldsInfo::Tuple { fields } => fields
+            .iter()
+            .any(|field| matches!(field, TupleFieldInfo::Header { .. })),
+    };
+    if !has_header {
+        return Err(syn::Error::new(
+            span,
+            "At least one #[mp4(header)] attribute required",
+        ));
+    }
+    let (assign_stmts, struct_return) = gen_read_fields(
+        name,
+        attrs,
+        fields_info,
+        TokenStream2::new(),
+        true,
+        span,
+        flavor,
+    )?;
+    let validate = gen_validate(attrs)?;
+
+    let derived = quote! {
+        let mut reader2 = reader.clone();
+        reader2.set_limit(header.body_size() as u64);
+        let reader2 = &mut reader2;
+        #assign_stmts
+        reader.seek_from_current(header.body_size() as i64)#await_?;
+        let value = #struct_return;
+        #validate
+        return Ok(value)
+    };
+    Ok(derived)
+}
+
+/// Fields up to the one marked `#[mp4(children)]` are read as in leaf boxes,
+/// the rest are child boxes collected as in internal boxes.
+fn gen_read_hybrid_struct_inner(
+    name: &TokenStream2,
+    generics: &Generics,
+    attrs: &Vec<Attribute>,
+    fields: &Fields,
+    span: Span,
+    flavor: &Flavor,
+) -> Result<TokenStream2, syn::Error> {
+    let Flavor {
+        module,
+        await_,
+        read_header,
+        ..
+    } = flavor;
+    let FieldsInfo::Struct {
+        header_name,
+        mut fields,
+    } = parse_fields(fields, span)?
+    else {
+        return Err(syn::Error::new(
+            span,
+            "boxtype = \"hybrid\" requires named fields",
+        ));
+    };
+    if header_name.is_none() {
+        return Err(syn::Error::new(
+            span,
+            "At least one #[mp4(header)] attribute required",
+        ));
+    }
+    let children_at = fields
+        .iter()
+        .position(|field| field.options.children)
+        .unwrap_or(fields.len());
+    let children = fields.split_off(children_at);
+    let child_fills =
+        children
+            .iter()
+            .map(child_fill)
+            .fold(TokenStream2::new(), |mut acc, tokens| {
+                acc.append_all(tokens);
+                acc
+            });
+    let (assign_stmts, struct_return) = gen_read_fields(
+        name,
+        attrs,
+        FieldsInfo::Struct {
+            header_name,
+            fields,
+        },
+        child_fills,
+        children.is_empty(),
+        span,
+        flavor,
+    )?;
+    let validate = gen_validate(attrs)?;
+    let read_children = if children.is_empty() {
+        TokenStream2::new()
+    } else {
+        let InternalCodeFlakes {
+            box_parsers,
+            placeholder_declations,
+            trailer_read,
+        } = gen_code_flakes_for_internal_from_struct(&children, generics, flavor)?;
+        quote! {
+            use #module::{BoxContainer, BoxPlaceholder};
+            let mut reader2 = reader2.clone();
+            #placeholder_declations
+            // shorter trailers (e.g. the zero terminator of some sample entries) are not boxes
+            while reader2.remain() >= 8 {
+                let header = #read_header?;
+                #box_parsers
+                reader2.seek_from_current(header.body_size() as i64)#await_?;
+            }
+            #trailer_read
         }
     };
 
     let derived = quote! {
         let mut reader2 = reader.clone();
         reader2.set_limit(header.body_size() as u64);
+        let reader2 = &mut reader2;
         #assign_stmts
-        reader.seek_from_current(header.body_size() as i64).await?;
-        return #struct_return
+        #read_children
+        reader.seek_from_current(header.body_size() as i64)#await_?;
+        let value = #struct_return;
+        #validate
+        return Ok(value)
     };
     Ok(derived)
 }
 
+/// Reads the body of a struct or an enum variant
+type GenReadInner = fn(
+    &TokenStream2,
+    &Generics,
+    &Vec<Attribute>,
+    &Fields,
+    Span,
+    &Flavor,
+) -> Result<TokenStream2, syn::Error>;
+
 fn gen_read_leaf_struct(
     name: &Ident,
+    generics: &Generics,
     attrs: &Vec<Attribute>,
     strct: &syn::DataStruct,
-) -> Result<TokenStream, syn::Error> {
-    let inner = gen_read_leaf_struct_inner(
+    gen_inner: GenReadInner,
+    flavor: &Flavor,
+) -> Result<TokenStream2, syn::Error> {
+    let Flavor {
+        module,
+        impl_attr,
+        read_body_sig,
+        ..
+    } = flavor;
+    let inner = gen_inner(
         &name.to_token_stream(),
+        generics,
+        attrs,
         &strct.fields,
         strct.struct_token.span,
+        flavor,
     )?;
 
-    let acceptable_tag = acceptable_tag(attrs, &strct.struct_token.span)?;
+    let acceptable = BoxTypes::parse(attrs, &strct.struct_token.span)?.gen_acceptable();
 
+    let (impl_generics, self_ty) = flavor.split_generics(name, generics);
+    let trait_args = flavor.trait_args();
+    let body = flavor.instrument(name, inner);
     let derived = quote! {
-        #[::async_trait::async_trait]
-        impl ::movparse_box::BoxRead for #name {
+        #impl_attr
+        impl #impl_generics #module::BoxRead #trait_args for #self_ty {
 
-            fn acceptable_tag(tag: [u8;4]) -> bool {
-                #acceptable_tag.contains(&tag)
+            #acceptable
+
+            #read_body_sig {
+                #body
             }
+        }
+    };
+    Ok(derived)
+}
 
-            async fn read_body<R: ::tokio::io::AsyncRead + ::tokio::io::AsyncSeek + ::std::marker::Unpin + ::std::marker::Send>(
-                header: ::movparse_box::BoxHeader,
-                reader: &mut ::movparse_box::Reader<R>,
-            ) -> std::io::Result<Self> {
-                #inner
+/// Condition of a field read only when `expr` holds
+struct Cond {
+    expr: TokenStream2,
+    /// `Option<T>` fields are `None` otherwise, other fields are `Either::Right`
+    option: bool,
+    if_version: bool,
+}
+
+/// Field-level `#[mp4(...)]` options of leaf boxes
+#[derive(Default)]
+struct FieldOptions {
+    fullbox: bool,
+    /// First child box of a hybrid box
+    children: bool,
+    /// Takes the child boxes no other field accepts
+    unknown: bool,
+    /// Records the order of the child boxes, as indices among the child fields, to write them back in that order
+    order: bool,
+    /// Last field, taking the rest of the body of a leaf box or the bytes after the last child box,
+    /// too few to be a box
+    trailer: bool,
+    cond: Option<Cond>,
+    /// Number of elements of a `Vec` field
+    count: Option<TokenStream2>,
+    /// Conditions the field must satisfy, kept as written for error messages
+    asserts: Vec<LitStr>,
+    /// Value the field must equal
+    magic: Option<TokenStream2>,
+    /// Module read and written with instead of `AttrRead`/`AttrWrite`. It provides `read` (async),
+    /// `read_blocking`, `size` and `write` (async), with the signatures of the trait methods
+    /// taking the value as their first argument.
+    with: Option<TokenStream2>,
+    /// Padding bytes before the field, skipped when reading and written as zeros
+    skip: Option<usize>,
+    /// Read and discarded, the field holds and is written as `Default::default()`
+    reserved: bool,
+    /// Computed instead of read from `Default::default()` or an expression of the earlier fields,
+    /// and not written
+    default: Option<TokenStream2>,
+    /// Width of a bit field, packed with the adjacent bit fields into whole bytes
+    bits: Option<u32>,
+}
+
+impl FieldOptions {
+    fn parse(attrs: &[Mp4Attr], ty: &Type) -> Result<Self, syn::Error> {
+        let mut options = Self::default();
+        for attr in attrs {
+            match attr {
+                Mp4Attr::Name(name) if name.is_ident("fullbox") => options.fullbox = true,
+                Mp4Attr::Name(name) if name.is_ident("children") => options.children = true,
+                Mp4Attr::Name(name) if name.is_ident("unknown") => options.unknown = true,
+                Mp4Attr::Name(name) if name.is_ident("order") => options.order = true,
+                Mp4Attr::Name(name) if name.is_ident("trailer") => options.trailer = true,
+                Mp4Attr::Name(name) if name.is_ident("reserved") => options.reserved = true,
+                Mp4Attr::Name(name) if name.is_ident("default") => {
+                    options.default = Some(quote! {::std::default::Default::default()})
+                }
+                Mp4Attr::WithValue(path, value) if path.is_ident("default") => {
+                    let syn::Lit::Str(s) = value else {
+                        return Err(syn::Error::new_spanned(
+                            value.to_token_stream(),
+                            "mp4(default = <expr str>)",
+                        ));
+                    };
+                    options.default = Some(s.parse::<Expr>()?.to_token_stream());
+                }
+                Mp4Attr::WithValue(path, value) if path.is_ident("with") => {
+                    let with = match value {
+                        syn::Lit::Str(s) => s.parse::<ExprPath>().ok(),
+                        _ => None,
+                    }
+                    .map(|with| with.to_token_stream());
+                    options.with = Some(with.ok_or_else(|| {
+                        syn::Error::new_spanned(
+                            value.to_token_stream(),
+                            "mp4(with = <module path>)",
+                        )
+                    })?);
+                }
+                Mp4Attr::WithValue(path, value) if path.is_ident("bits") => {
+                    let bits = match value {
+                        syn::Lit::Int(bits) => bits.base10_parse::<u32>().ok(),
+                        _ => None,
+                    };
+                    options.bits =
+                        Some(bits.filter(|bits| (1..=64).contains(bits)).ok_or_else(|| {
+                            syn::Error::new_spanned(value.to_token_stream(), "mp4(bits = <1..=64>)")
+                        })?);
+                }
+                Mp4Attr::WithValue(path, value) if path.is_ident("skip") => {
+                    let syn::Lit::Int(skip) = value else {
+                        return Err(syn::Error::new_spanned(
+                            value.to_token_stream(),
+                            "mp4(skip = <byte count>)",
+                        ));
+                    };
+                    options.skip = Some(skip.base10_parse::<usize>()?);
+                }
+                Mp4Attr::WithValue(path, value) if path.is_ident("cond") => {
+                    let syn::Lit::Str(s) = value else {
+                        return Err(syn::Error::new_spanned(
+                            value.to_token_stream(),
+                            "mp4(cond = <expr str>)",
+                        ));
+                    };
+                    let expr = s.parse::<Expr>()?;
+                    options.cond = Some(Cond {
+                        expr: expr.to_token_stream(),
+                        option: is_option(ty),
+                        if_version: false,
+                    });
+                }
+                Mp4Attr::WithValue(path, value) if path.is_ident("count") => {
+                    let syn::Lit::Str(s) = value else {
+                        return Err(syn::Error::new_spanned(
+                            value.to_token_stream(),
+                            "mp4(count = <expr str>)",
+                        ));
+                    };
+                    options.count = Some(s.parse::<Expr>()?.to_token_stream());
+                }
+                Mp4Attr::WithValue(path, value) if path.is_ident("assert") => {
+                    let syn::Lit::Str(s) = value else {
+                        return Err(syn::Error::new_spanned(
+                            value.to_token_stream(),
+                            "mp4(assert = <expr str>)",
+                        ));
+                    };
+                    s.parse::<Expr>()?;
+                    options.asserts.push(s.clone());
+                }
+                Mp4Attr::WithValue(path, value) if path.is_ident("magic") => {
+                    options.magic = Some(match value {
+                        syn::Lit::Int(_) => value.to_token_stream(),
+                        // compared by value with `[u8; N]` fields
+                        syn::Lit::ByteStr(_) => quote! {*#value},
+                        syn::Lit::Str(s) => s.parse::<Expr>()?.to_token_stream(),
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                value.to_token_stream(),
+                                "mp4(magic = <int, byte str or expr str>)",
+                            ))
+                        }
+                    });
+                }
+                Mp4Attr::WithValue(path, value) if path.is_ident("if_version") => {
+                    let syn::Lit::Int(version) = value else {
+                        return Err(syn::Error::new_spanned(
+                            value.to_token_stream(),
+                            "mp4(if_version = <u8>)",
+                        ));
+                    };
+                    let version = version.base10_parse::<u8>()?;
+                    options.cond = Some(Cond {
+                        expr: quote! {version == #version},
+                        option: is_option(ty),
+                        if_version: true,
+                    });
+                }
+                _ => (),
             }
         }
+        if options.default.is_some()
+            && (options.with.is_some()
+                || options.reserved
+                || options.cond.is_some()
+                || options.count.is_some())
+        {
+            return Err(syn::Error::new_spanned(
+                ty,
+                "#[mp4(default)] fields are not read, remove #[mp4(with/reserved/cond/count)]",
+            ));
+        }
+        if options.bits.is_some()
+            && (options.fullbox
+                || options.cond.is_some()
+                || options.count.is_some()
+                || options.with.is_some()
+                || options.skip.is_some()
+                || options.reserved
+                || options.default.is_some())
+        {
+            return Err(syn::Error::new_spanned(
+                ty,
+                "#[mp4(bits)] only supports #[mp4(assert)] and #[mp4(magic)]",
+            ));
+        }
+        if is_option(ty) && options.cond.is_none() && options.count.is_some() {
+            return Err(syn::Error::new_spanned(
+                ty,
+                "#[mp4(count)] on an Option field requires #[mp4(cond)]",
+            ));
+        }
+        if (options.order || options.trailer)
+            && (options.leaf_only().is_some() || options.unknown || options.children)
+        {
+            return Err(syn::Error::new_spanned(
+                ty,
+                "#[mp4(order)] and #[mp4(trailer)] fields take no other options",
+            ));
+        }
+        if options.with.is_some() && options.count.is_some() {
+            return Err(syn::Error::new_spanned(
+                ty,
+                "#[mp4(with)] reads the whole field, remove #[mp4(count)]",
+            ));
+        }
+        Ok(options)
+    }
+
+    /// Options that only make sense between the fields of a leaf box
+    fn leaf_only(&self) -> Option<&'static str> {
+        if self.fullbox {
+            Some("#[mp4(fullbox)]")
+        } else if self.cond.is_some() {
+            Some("#[mp4(cond)] and #[mp4(if_version)]")
+        } else if self.count.is_some() {
+            Some("#[mp4(count)]")
+        } else if !self.asserts.is_empty() || self.magic.is_some() {
+            Some("#[mp4(assert)] and #[mp4(magic)]")
+        } else if self.with.is_some() || self.skip.is_some() {
+            Some("#[mp4(with)] and #[mp4(skip)]")
+        } else if self.reserved || self.default.is_some() {
+            Some("#[mp4(reserved)] and #[mp4(default)]")
+        } else if self.bits.is_some() {
+            Some("#[mp4(bits)]")
+        } else {
+            None
+        }
+    }
+}
+
+fn is_option(ty: &Type) -> bool {
+    let Type::Path(path) = ty else {
+        return false;
     };
-    Ok(derived.into())
+    path.path
+        .segments
+        .last()
+        .is_some_and(|segment| segment.ident == "Option")
 }
 
 struct StructFieldInfo {
     ty: TokenStream2,
+    /// Type as written in the struct, `ty` is its expression form
+    decl_ty: Type,
     field_name: Ident,
     field_name_str_lit: LitStr,
     allocated_name: Ident,
+    options: FieldOptions,
 }
 
 enum TupleFieldInfo {
     NormalField {
         ty: TokenStream2,
+        decl_ty: Box<Type>,
         allocated_name: Ident,
+        options: Box<FieldOptions>,
     },
     Header {
         allocated_name: Ident,
@@ -200,17 +1560,11 @@ fn canonicalize_ty(ty: &Type) -> TokenStream2 {
     match ty {
         syn::Type::Path(path) => {
             let mut tokens = Punctuated::<_, Colon2>::new();
-            for (idx, segment) in (&path.path.segments).into_iter().enumerate() {
+            for segment in &path.path.segments {
                 let ident = &segment.ident;
-                if idx == 0 {
-                    tokens.push(quote! {
-                        #ident
-                    });
-                } else {
-                    tokens.push(quote! {
-                        ::#ident
-                    });
-                }
+                tokens.push(quote! {
+                    #ident
+                });
                 if !segment.arguments.is_empty() {
                     let arguments = &segment.arguments;
                     tokens.push(quote! {
@@ -218,7 +1572,10 @@ fn canonicalize_ty(ty: &Type) -> TokenStream2 {
                     });
                 }
             }
-            tokens.to_token_stream()
+            let leading_colon = &path.path.leading_colon;
+            quote! {
+                #leading_colon #tokens
+            }
         }
         _ => quote! {
             <#ty>
@@ -252,11 +1609,17 @@ fn parse_fields(fields: &Fields, span: Span) -> Result<FieldsInfo, syn::Error> {
                 let field_name_str_lit = LitStr::new(&field_name.to_string(), field_name.span());
                 let allocated_name =
                     Ident::new(&format!("field{}", idx), field.into_token_stream().span());
+                let options = match FieldOptions::parse(&attrs, &field.ty) {
+                    Ok(options) => options,
+                    Err(e) => return Either::Right(Err(e)),
+                };
                 Either::Right(Ok::<_, syn::Error>(StructFieldInfo {
                     ty: canonicalize_ty(&field.ty),
+                    decl_ty: field.ty.clone(),
                     field_name: field_name.clone(),
                     field_name_str_lit,
                     allocated_name,
+                    options,
                 }))
             });
         if headers.len() > 1 {
@@ -267,6 +1630,29 @@ fn parse_fields(fields: &Fields, span: Span) -> Result<FieldsInfo, syn::Error> {
         }
         let header = headers.into_iter().next();
         let fields = fields.into_iter().collect::<Result<Vec<_>, _>>()?;
+        if fields.iter().filter(|field| field.options.fullbox).count() > 1 {
+            return Err(syn::Error::new(
+                span,
+                "#[mp4(fullbox)] attribute must be one".to_owned(),
+            ));
+        }
+        if let Some(field) = fields.iter().filter(|field| field.options.order).nth(1) {
+            return Err(syn::Error::new(
+                field.field_name.span(),
+                "#[mp4(order)] attribute must be one",
+            ));
+        }
+        if let Some(field) = fields
+            .iter()
+            .rev()
+            .skip(1)
+            .find(|field| field.options.trailer)
+        {
+            return Err(syn::Error::new(
+                field.field_name.span(),
+                "#[mp4(trailer)] must be on the last field",
+            ));
+        }
         Ok(FieldsInfo::Struct {
             header_name: header.cloned(),
             fields,
@@ -292,7 +1678,9 @@ fn parse_fields(fields: &Fields, span: Span) -> Result<FieldsInfo, syn::Error> {
                     Ident::new(&format!("field{}", idx), field.into_token_stream().span());
                 Ok::<_, syn::Error>(TupleFieldInfo::NormalField {
                     ty: canonicalize_ty(&field.ty),
+                    decl_ty: Box::new(field.ty.clone()),
                     allocated_name,
+                    options: Box::new(FieldOptions::parse(&attrs, &field.ty)?),
                 })
             })
             .collect::<Result<Vec<_>, _>>()?;
@@ -307,15 +1695,46 @@ fn parse_fields(fields: &Fields, span: Span) -> Result<FieldsInfo, syn::Error> {
                 "#[movparse(header)] attribute must be one".to_owned(),
             ));
         }
+        if fields
+            .iter()
+            .filter(|field| {
+                matches!(field, TupleFieldInfo::NormalField { options, .. } if options.fullbox)
+            })
+            .count()
+            > 1
+        {
+            return Err(syn::Error::new(
+                span,
+                "#[mp4(fullbox)] attribute must be one".to_owned(),
+            ));
+        }
+        if let Some(TupleFieldInfo::NormalField { allocated_name, .. }) = fields.iter().find(|field| {
+            matches!(field, TupleFieldInfo::NormalField { options, .. } if options.order || options.trailer)
+        }) {
+            return Err(syn::Error::new(
+                allocated_name.span(),
+                "#[mp4(order)] and #[mp4(trailer)] require named fields",
+            ));
+        }
         Ok(FieldsInfo::Tuple { fields })
     }
 }
 
-fn derive_mp4_root_read_for_struct(
-    name: &Ident,
-    strct: &syn::DataStruct,
-) -> Result<TokenStream, syn::Error> {
-    let fields_info = parse_fields(&strct.fields, strct.fields.span())?;
+/// Reads the top-level boxes of a struct or an enum variant
+#[allow(clippy::manual_try_fold)]
+fn gen_root_read_inner(
+    name: &TokenStream2,
+    generics: &Generics,
+    fields: &Fields,
+    flavor: &Flavor,
+) -> Result<TokenStream2, syn::Error> {
+    let Flavor {
+        module,
+        await_,
+        read_header,
+        ..
+    } = flavor;
+    let fields_info = parse_fields(fields, fields.span())?;
     let (placeholders, parsers, struct_return) = match fields_info {
         FieldsInfo::Struct {
             header_name,
@@ -327,35 +1746,17 @@ fn derive_mp4_root_read_for_struct(
                     "RootRead cannot has header".to_owned(),
                 ));
             }
-            let parsers = fields
-                .iter()
-                .map(|field| {
-                    let allocated_name = &field.allocated_name;
-                    quote! {
-                        if #allocated_name.acceptable_tag(header.id) {
-                            let value = #allocated_name.read_body(header, &mut reader2).await?;
-                            ::movparse_box::BoxPlaceholder::push(&mut #allocated_name, value)?;
-                            continue;
-                        }
-                    }
-                })
-                .fold(TokenStream2::new(), |mut acc, tokens| {
-                    acc.append_all(tokens);
-                    acc
-                });
-            let placeholders = fields
+            if let Some(field) = fields
                 .iter()
-                .map(|field| {
-                    let ty = &field.ty;
-                    let allocated_name = &field.allocated_name;
-                    quote! {
-                        let mut #allocated_name = #ty::placeholder();
-                    }
-                })
-                .fold(TokenStream2::new(), |mut acc, tokens| {
-                    acc.append_all(tokens);
-                    acc
-                });
+                .find(|field| field.options.order || field.options.trailer)
+            {
+                return Err(syn::Error::new(
+                    field.field_name.span(),
+                    "#[mp4(order)] and #[mp4(trailer)] are not supported by RootRead",
+                ));
+            }
+            let internal_code_flakes =
+                gen_code_flakes_for_internal_from_struct(&fields, generics, flavor)?;
             let struct_fills = fields
                 .iter()
                 .map(|field| {
@@ -363,7 +1764,7 @@ fn derive_mp4_root_read_for_struct(
                     let allocated_name = &field.allocated_name;
                     let field_name_str = &field.field_name_str_lit;
                     quote! {
-                        #actual_name: #allocated_name.get(#field_name_str)?,
+                        #actual_name: #allocated_name.get(#field_name_str).map_err(|kind| reader.error(kind))?,
                     }
                 })
                 .fold(TokenStream2::new(), |mut acc, tokens| {
@@ -371,32 +1772,34 @@ fn derive_mp4_root_read_for_struct(
                     acc
                 });
             let struct_return = quote! {
-                Ok(Self {
+                Ok(#name {
                     #struct_fills
                 })
             };
-            (placeholders, parsers, struct_return)
+            (
+                internal_code_flakes.placeholder_declations,
+                internal_code_flakes.box_parsers,
+                struct_return,
+            )
         }
         FieldsInfo::Tuple { fields } => {
-            let internal_code_flakes = gen_code_flakes_for_internal_from_tuple(&fields)?;
+            let internal_code_flakes =
+                gen_code_flakes_for_internal_from_tuple(&fields, generics, flavor)?;
             let struct_fills = fields
                 .iter()
                 .enumerate()
                 .map(|(idx, field)| match field {
                     TupleFieldInfo::Header { allocated_name } => Err(syn::Error::new(
                         allocated_name.span(),
-                        "#[movparse(header)] is unrecognized option for RootRead",
-                    )),
-                    TupleFieldInfo::NormalField {
-                        ty: _,
-                        allocated_name,
-                    } => {
+                        "#[movparse(header)] is unrecognized option for RootRead",
+                    )),
+                    TupleFieldInfo::NormalField { allocated_name, .. } => {
                         let field_name_str = syn::Lit::Str(syn::LitStr::new(
                             &format!("${}", idx),
                             allocated_name.span(),
                         ));
                         Ok(quote! {
-                            #allocated_name.get(#field_name_str)?,
+                            #allocated_name.get(#field_name_str).map_err(|kind| reader.error(kind))?,
                         })
                     }
                 })
@@ -407,7 +1810,7 @@ fn derive_mp4_root_read_for_struct(
                     Ok(acc)
                 })?;
             let struct_return = quote! {
-                Ok(Self(
+                Ok(#name(
                     #struct_fills
                 ))
             };
@@ -419,89 +1822,227 @@ fn derive_mp4_root_read_for_struct(
         }
     };
     let derived = quote! {
-        #[::async_trait::async_trait]
-        impl ::movparse_box::RootRead for #name {
-            async fn read<R: ::tokio::io::AsyncRead + ::tokio::io::AsyncSeek + ::std::marker::Unpin + ::std::marker::Send>(
-                reader: &mut ::movparse_box::Reader<R>,
-            ) -> std::io::Result<Self> {
-                use ::movparse_box::{BoxContainer, BoxPlaceholder};
-                let mut reader2 = reader.clone();
-                #placeholders
-                while reader2.remain() > 0 {
-                    let header = ::movparse_box::BoxHeader::read(&mut reader2).await?;
-                    #parsers
-                    reader2.seek_from_current(header.body_size() as i64).await?;
-                }
-                #struct_return
+        use #module::{BoxContainer, BoxPlaceholder};
+        let mut reader2 = reader.clone();
+        #placeholders
+        while reader2.remain() > 0 {
+            let header = #read_header?;
+            #parsers
+            reader2.seek_from_current(header.body_size() as i64)#await_?;
+        }
+        return #struct_return
+    };
+    Ok(derived)
+}
+
+fn derive_mp4_root_read_for_struct(
+    name: &Ident,
+    generics: &Generics,
+    strct: &syn::DataStruct,
+    flavor: &Flavor,
+) -> Result<TokenStream2, syn::Error> {
+    let Flavor {
+        module,
+        impl_attr,
+        root_read_sig,
+        ..
+    } = flavor;
+    let inner = gen_root_read_inner(&quote! {Self}, generics, &strct.fields, flavor)?;
+    let (impl_generics, self_ty) = flavor.split_generics(name, generics);
+    let trait_args = flavor.trait_args();
+    let derived = quote! {
+        #impl_attr
+        impl #impl_generics #module::RootRead #trait_args for #self_ty {
+            #root_read_sig {
+                #inner
             }
         }
     };
-    Ok(derived.into())
+    Ok(derived)
+}
+
+/// Condition on the scanned `top` selecting a variant, from `#[mp4(brand = "...")]` (any of them)
+/// and `#[mp4(has = "...")]` (all of them). Variants without either always match.
+fn root_variant_cond(variant: &syn::Variant) -> Result<TokenStream2, syn::Error> {
+    let mut brands = Vec::new();
+    let mut has = Vec::new();
+    for attr in parse_mp4_attrs(&variant.attrs)? {
+        if let Mp4Attr::WithValue(path, value) = attr {
+            if path.is_ident("brand") {
+                brands.push(parse_fourcc(
+                    &value,
+                    "mp4(brand = <4 char str, 4 byte str or u32>)",
+                )?);
+            } else if path.is_ident("has") {
+                has.push(parse_fourcc(
+                    &value,
+                    "mp4(has = <4 char str, 4 byte str or u32>)",
+                )?);
+            }
+        }
+    }
+    let by_brand = if brands.is_empty() {
+        quote! {true}
+    } else {
+        let brands = brands.iter().map(|brand| quote! {[#(#brand),*]});
+        quote! {[#(#brands),*].iter().any(|brand| top.has_brand(*brand))}
+    };
+    let has = has.iter().map(|id| quote! {top.contains([#(#id),*])});
+    Ok(quote! {#by_brand #(&& #has)*})
 }
 
-fn gen_read_leaf_enum(name: &Ident, enm: &syn::DataEnum) -> Result<TokenStream, syn::Error> {
+fn derive_mp4_root_read_for_enum(
+    name: &Ident,
+    generics: &Generics,
+    enm: &syn::DataEnum,
+    flavor: &Flavor,
+) -> Result<TokenStream2, syn::Error> {
+    let Flavor {
+        module,
+        impl_attr,
+        root_read_sig,
+        scan_top_level,
+        ..
+    } = flavor;
     let blocks = enm
         .variants
         .iter()
         .map(|variant| {
             let name = &variant.ident;
             let inner =
-                gen_read_leaf_struct_inner(&quote! {Self::#name}, &variant.fields, variant.span())?;
-            let acceptable_tag = acceptable_tag(&variant.attrs, &variant.span())?;
+                gen_root_read_inner(&quote! {Self::#name}, generics, &variant.fields, flavor)?;
+            let cond = root_variant_cond(variant)?;
             Ok::<_, syn::Error>(quote! {
-                if #acceptable_tag.contains(&header.id) {
+                if #cond {
                     #inner
                 }
             })
         })
-        .fold(Ok::<_, syn::Error>(TokenStream2::new()), |acc, block| {
-            let mut acc = acc?;
-            let block = block?;
-            acc.append_all(block);
-            Ok(acc)
+        .try_fold(TokenStream2::new(), |mut acc, block| {
+            acc.append_all(block?);
+            Ok::<_, syn::Error>(acc)
         })?;
-
-    let attrs_for_gen_acceptable_tag = enm
-        .variants
-        .iter()
-        .flat_map(|variant| variant.attrs.clone())
-        .collect_vec();
-
-    let acceptable_tag = acceptable_tag(&attrs_for_gen_acceptable_tag, &enm.enum_token.span)?;
-
+    let (impl_generics, self_ty) = flavor.split_generics(name, generics);
+    let trait_args = flavor.trait_args();
     let derived = quote! {
-        #[::async_trait::async_trait]
-        impl ::movparse_box::BoxRead for #name {
-            fn acceptable_tag(tag: [u8;4]) -> bool {
-                #acceptable_tag.contains(&tag)
-            }
-
-            async fn read_body<R: ::tokio::io::AsyncRead + ::tokio::io::AsyncSeek + ::std::marker::Unpin + ::std::marker::Send>(
-                header: ::movparse_box::BoxHeader,
-                reader: &mut ::movparse_box::Reader<R>,
-            ) -> std::io::Result<Self> {
+        #impl_attr
+        impl #impl_generics #module::RootRead #trait_args for #self_ty {
+            #root_read_sig {
+                let top = #scan_top_level?;
                 #blocks
-                unreachable!()
+                Err(reader.error(::movparse_box::ErrorKind::UnknownFileType {
+                    brands: top
+                        .brands
+                        .iter()
+                        .map(|brand| ::movparse_box::U32Tag { raw: *brand })
+                        .collect(),
+                }))
             }
         }
     };
-    Ok(derived.into())
+    Ok(derived)
 }
 
-fn gen_read_internal_enum(name: &Ident, enm: &syn::DataEnum) -> Result<TokenStream, syn::Error> {
-    let blocks = enm
+/// Variant marked `#[mp4(unknown)]`, which takes every box no other variant accepts
+fn fallback_variant(enm: &syn::DataEnum) -> Result<Option<&syn::Variant>, syn::Error> {
+    let mut fallbacks = Vec::new();
+    for variant in &enm.variants {
+        if parse_mp4_attrs(&variant.attrs)?
+            .iter()
+            .any(|attr| matches!(attr, Mp4Attr::Name(name) if name.is_ident("unknown")))
+        {
+            if !matches!(&variant.fields, Fields::Unnamed(fields) if fields.unnamed.len() == 1) {
+                return Err(syn::Error::new(
+                    variant.span(),
+                    "#[mp4(unknown)] variant must have one unnamed field, e.g. Unknown(RawBox)",
+                ));
+            }
+            fallbacks.push(variant);
+        }
+    }
+    if let Some(variant) = fallbacks.get(1) {
+        return Err(syn::Error::new(
+            variant.span(),
+            "#[mp4(unknown)] attribute must be one",
+        ));
+    }
+    Ok(fallbacks.into_iter().next())
+}
+
+/// Rejects variants accepting the same tag or uuid, since only the first of them would ever be read
+fn check_distinct_variants(
+    variants: &[&syn::Variant],
+    types: &[BoxTypes],
+) -> Result<(), syn::Error> {
+    for ((first, first_types), (second, second_types)) in
+        variants.iter().zip(types).tuple_combinations()
+    {
+        let conflict = if let Some(tag) = first_types
+            .tags
+            .iter()
+            .find(|tag| second_types.tags.contains(tag))
+        {
+            format!("tag \"{}\"", tag.escape_ascii())
+        } else if let Some(uuid) = first_types
+            .uuids
+            .iter()
+            .find(|uuid| second_types.uuids.contains(uuid))
+        {
+            format!(
+                "uuid {}",
+                uuid.iter().map(|b| format!("{:02x}", b)).join("")
+            )
+        } else {
+            continue;
+        };
+        return Err(syn::Error::new(
+            second.ident.span(),
+            format!(
+                "variants `{}` and `{}` both accept {}",
+                first.ident, second.ident, conflict
+            ),
+        ));
+    }
+    Ok(())
+}
+
+#[allow(clippy::manual_try_fold)]
+fn gen_read_enum(
+    name: &Ident,
+    generics: &Generics,
+    enm: &syn::DataEnum,
+    gen_inner: GenReadInner,
+    flavor: &Flavor,
+) -> Result<TokenStream2, syn::Error> {
+    let Flavor {
+        module,
+        await_,
+        impl_attr,
+        read_body_sig,
+        ..
+    } = flavor;
+    let fallback = fallback_variant(enm)?;
+    let variants = enm
         .variants
+        .iter()
+        .filter(|variant| fallback.is_none_or(|fallback| fallback.ident != variant.ident))
+        .collect_vec();
+    let blocks = variants
         .iter()
         .map(|variant| {
             let name = &variant.ident;
-            let inner = gen_read_internal_struct_inner(
+            let inner = gen_inner(
                 &quote! {Self::#name},
+                generics,
+                &variant.attrs,
                 &variant.fields,
                 variant.span(),
+                flavor,
             )?;
-            let acceptable_tag = acceptable_tag(&variant.attrs, &variant.span())?;
+            let accepts_header =
+                BoxTypes::parse(&variant.attrs, &variant.span())?.accepts_header(quote! {header});
             Ok::<_, syn::Error>(quote! {
-                if #acceptable_tag.contains(&header.id) {
+                if #accepts_header {
                     #inner
                 }
             })
@@ -513,49 +2054,239 @@ fn gen_read_internal_enum(name: &Ident, enm: &syn::DataEnum) -> Result<TokenStre
             Ok(acc)
         })?;
 
-    let attrs_for_gen_acceptable_tag = enm
-        .variants
+    let types = variants
         .iter()
-        .flat_map(|variant| variant.attrs.clone())
-        .collect_vec();
+        .map(|variant| BoxTypes::parse(&variant.attrs, &variant.span()))
+        .collect::<Result<Vec<_>, _>>()?;
+    check_distinct_variants(&variants, &types)?;
+    let (acceptable, otherwise) = match fallback {
+        Some(fallback) => {
+            let fallback = &fallback.ident;
+            (
+                quote! {
+                    fn acceptable_tag(_: [u8;4]) -> bool {
+                        true
+                    }
 
-    let acceptable_tag = acceptable_tag(&attrs_for_gen_acceptable_tag, &enm.enum_token.span)?;
+                    fn acceptable_header(_: &::movparse_box::BoxHeader) -> bool {
+                        true
+                    }
+                },
+                quote! {
+                    Ok(Self::#fallback(#module::BoxRead::read_body(header, reader)#await_?))
+                },
+            )
+        }
+        None => (
+            BoxTypes::merge(types).gen_acceptable(),
+            quote! {
+                Err(reader.error(::movparse_box::ErrorKind::UnexpectedTag {
+                    tag: ::movparse_box::U32Tag { raw: header.id },
+                }))
+            },
+        ),
+    };
 
+    let (impl_generics, self_ty) = flavor.split_generics(name, generics);
+    let trait_args = flavor.trait_args();
+    let body = flavor.instrument(name, quote! {#blocks #otherwise});
     let derived = quote! {
-        fn acceptable_tag(tag: [u8;4]) -> bool {
-            #acceptable_tag.contains(&tag)
-        }
+        #impl_attr
+        impl #impl_generics #module::BoxRead #trait_args for #self_ty {
+            #acceptable
 
-        #[::async_trait::async_trait]
-        impl ::movparse_box::BoxRead for #name {
-            async fn read_body<R: ::tokio::io::AsyncRead + ::tokio::io::AsyncSeek + ::std::marker::Unpin + ::std::marker::Send>(
-                header: ::movparse_box::BoxHeader,
-                reader: &mut ::movparse_box::Reader<R>,
-            ) -> std::io::Result<Self> {
-                #blocks
-                unreachable!()
+            #read_body_sig {
+                #body
             }
         }
     };
-    Ok(derived.into())
+    Ok(derived)
 }
 
 struct InternalCodeFlakes {
     box_parsers: TokenStream2,
     placeholder_declations: TokenStream2,
+    /// Reads the bytes after the last child into the `#[mp4(trailer)]` field, if any
+    trailer_read: TokenStream2,
+}
+
+/// Compile-time assertions that no two child fields, given by name, type and span, accept the same box type,
+/// as only the first field matching a box would ever receive it.
+/// They are evaluated when the reader is instantiated, so they are only emitted for the pairs involving a type
+/// depending on type parameters; the other pairs are checked up front by [`gen_tag_check_items`].
+fn gen_tag_checks(
+    fields: &[(String, &Type, Span)],
+    generics: &Generics,
+    child_tags: &TokenStream2,
+) -> TokenStream2 {
+    fields
+        .iter()
+        .tuple_combinations()
+        .filter(|((_, first_ty, _), (_, second_ty, _))| {
+            mentions_generics(first_ty.to_token_stream(), generics)
+                || mentions_generics(second_ty.to_token_stream(), generics)
+        })
+        .map(|((first, first_ty, _), (second, second_ty, span))| {
+            let message = format!(
+                "fields `{}` and `{}` accept the same box type",
+                first, second
+            );
+            quote_spanned! {*span=>
+                const {
+                    assert!(
+                        !::movparse_box::tags_overlap(
+                            <#first_ty as #child_tags>::TAGS,
+                            <#second_ty as #child_tags>::TAGS,
+                        ),
+                        #message
+                    )
+                };
+            }
+        })
+        .collect()
+}
+
+/// Name, type and span of the child fields of a struct or variant, the fields after `#[mp4(children)]` if `hybrid`
+fn child_fields(fields: &Fields, hybrid: bool) -> syn::Result<Vec<(String, Type, Span)>> {
+    let children = match parse_fields(fields, fields.span())? {
+        FieldsInfo::Struct { fields, .. } => {
+            let children_at = if hybrid {
+                fields
+                    .iter()
+                    .position(|field| field.options.children)
+                    .unwrap_or(fields.len())
+            } else {
+                0
+            };
+            fields
+                .into_iter()
+                .skip(children_at)
+                .filter(|field| {
+                    !field.options.unknown && !field.options.order && !field.options.trailer
+                })
+                .map(|field| {
+                    (
+                        field.field_name.to_string(),
+                        field.decl_ty,
+                        field.field_name.span(),
+                    )
+                })
+                .collect_vec()
+        }
+        FieldsInfo::Tuple { fields } => fields
+            .into_iter()
+            .enumerate()
+            .flat_map(|(idx, field)| match field {
+                TupleFieldInfo::NormalField {
+                    decl_ty,
+                    allocated_name,
+                    options,
+                    ..
+                } if !options.unknown && !hybrid => {
+                    Some((format!("${}", idx), *decl_ty, allocated_name.span()))
+                }
+                _ => None,
+            })
+            .collect_vec(),
+    };
+    Ok(children)
+}
+
+/// Whether `tokens` name a type, lifetime or const parameter of `generics`
+fn mentions_generics(tokens: TokenStream2, generics: &Generics) -> bool {
+    tokens.into_iter().any(|token| match token {
+        proc_macro2::TokenTree::Ident(ident) => generics.params.iter().any(|param| match param {
+            syn::GenericParam::Type(param) => param.ident == ident,
+            syn::GenericParam::Lifetime(param) => param.lifetime.ident == ident,
+            syn::GenericParam::Const(param) => param.ident == ident,
+        }),
+        proc_macro2::TokenTree::Group(group) => mentions_generics(group.stream(), generics),
+        _ => false,
+    })
+}
+
+/// `const _` items asserting that no two child fields of a struct or of any variant accept the same box type.
+/// Unlike [`gen_tag_checks`], they are evaluated by `cargo check` even if the reader is never used,
+/// so they are emitted once next to the impls, for the fields whose types do not depend on type parameters.
+fn gen_tag_check_items(
+    generics: &Generics,
+    data: &syn::Data,
+    hybrid: bool,
+    child_tags: &TokenStream2,
+) -> syn::Result<TokenStream2> {
+    let fields = match data {
+        syn::Data::Struct(strct) => vec![&strct.fields],
+        syn::Data::Enum(enm) => enm.variants.iter().map(|variant| &variant.fields).collect(),
+        syn::Data::Union(_) => Vec::new(),
+    };
+    let mut items = TokenStream2::new();
+    for fields in fields {
+        let children = child_fields(fields, hybrid)?
+            .into_iter()
+            .filter(|(_, ty, _)| !mentions_generics(ty.to_token_stream(), generics))
+            .collect_vec();
+        for ((first, first_ty, _), (second, second_ty, span)) in
+            children.iter().tuple_combinations()
+        {
+            let message = format!(
+                "fields `{}` and `{}` accept the same box type",
+                first, second
+            );
+            items.append_all(quote_spanned! {*span=>
+                const _: () = assert!(
+                    !::movparse_box::tags_overlap(
+                        <#first_ty as #child_tags>::TAGS,
+                        <#second_ty as #child_tags>::TAGS,
+                    ),
+                    #message
+                );
+            });
+        }
+    }
+    Ok(items)
 }
 
 fn gen_code_flakes_for_internal_from_struct(
     fields: &[StructFieldInfo],
+    generics: &Generics,
+    flavor: &Flavor,
 ) -> syn::Result<InternalCodeFlakes> {
-    let box_parsers = fields
+    let Flavor { module, await_, .. } = flavor;
+    for field in fields {
+        if let Some(attr) = field.options.leaf_only() {
+            return Err(syn::Error::new(
+                field.field_name.span(),
+                format!("{} is only supported by boxtype = \"leaf\"", attr),
+            ));
+        }
+    }
+    if let Some(field) = fields.iter().filter(|field| field.options.unknown).nth(1) {
+        return Err(syn::Error::new(
+            field.field_name.span(),
+            "#[mp4(unknown)] attribute must be one",
+        ));
+    }
+    let order = fields.iter().find(|field| field.options.order);
+    let trailer = fields.iter().find(|field| field.options.trailer);
+    let children = fields
         .iter()
-        .map(|field| {
+        .filter(|field| !field.options.order && !field.options.trailer)
+        .collect_vec();
+    // the unknown field accepts any box, so it is tried last
+    let box_parsers = children
+        .iter()
+        .enumerate()
+        .sorted_by_key(|(_, field)| field.options.unknown)
+        .map(|(idx, field)| {
             let allocated_name = &field.allocated_name;
+            let record = order.map(|order| {
+                let order = &order.allocated_name;
+                quote! {#order.push(#idx);}
+            });
             quote! {
-                if #allocated_name.acceptable_tag(header.id) {
-                    let value = #allocated_name.read_body(header, &mut reader2).await?;
-                    ::movparse_box::BoxPlaceholder::push(&mut #allocated_name, value)?;
+                if #allocated_name.acceptable_header(&header) {
+                    #module::BoxPlaceholder::read_child(&mut #allocated_name, header, &mut reader2)#await_?;
+                    #record
                     continue;
                 }
             }
@@ -564,39 +2295,111 @@ fn gen_code_flakes_for_internal_from_struct(
             acc.append_all(tokens);
             acc
         });
+    let trailer_read = trailer
+        .map(|trailer| {
+            let allocated_name = &trailer.allocated_name;
+            quote! {
+                #allocated_name = #module::AttrRead::read_attr(&mut reader2)#await_?;
+            }
+        })
+        .unwrap_or_default();
+    let tag_checks = gen_tag_checks(
+        &children
+            .iter()
+            .filter(|field| !field.options.unknown)
+            .map(|field| {
+                (
+                    field.field_name.to_string(),
+                    &field.decl_ty,
+                    field.field_name.span(),
+                )
+            })
+            .collect_vec(),
+        generics,
+        &flavor.child_tags,
+    );
     let placeholder_declations = fields
         .iter()
         .map(|field| {
             let ty = &field.ty;
+            let decl_ty = &field.decl_ty;
             let allocated_name = &field.allocated_name;
-            quote! {
-                let mut #allocated_name = #ty::placeholder();
+            if field.options.order || field.options.trailer {
+                quote! {
+                    let mut #allocated_name = <#decl_ty as ::std::default::Default>::default();
+                }
+            } else {
+                quote! {
+                    let mut #allocated_name = #ty::placeholder();
+                }
             }
         })
-        .fold(TokenStream2::new(), |mut acc, tokens| {
+        .fold(tag_checks, |mut acc, tokens| {
             acc.append_all(tokens);
             acc
         });
     Ok(InternalCodeFlakes {
         placeholder_declations,
         box_parsers,
+        trailer_read,
     })
 }
 
+/// Fill of a field of a box with children, from the placeholder or the value collected while reading them
+fn child_fill(field: &StructFieldInfo) -> TokenStream2 {
+    let actual_name = &field.field_name;
+    let allocated_name = &field.allocated_name;
+    let field_name_str = &field.field_name_str_lit;
+    if field.options.order || field.options.trailer {
+        quote! {
+            #actual_name: #allocated_name,
+        }
+    } else {
+        quote! {
+            #actual_name: #allocated_name.get(#field_name_str).map_err(|kind| reader.error(kind))?,
+        }
+    }
+}
+
 fn gen_code_flakes_for_internal_from_tuple(
     fields: &[TupleFieldInfo],
+    generics: &Generics,
+    flavor: &Flavor,
 ) -> syn::Result<InternalCodeFlakes> {
+    let Flavor { module, await_, .. } = flavor;
+    for field in fields {
+        if let TupleFieldInfo::NormalField {
+            allocated_name,
+            options,
+            ..
+        } = field
+        {
+            if let Some(attr) = options.leaf_only() {
+                return Err(syn::Error::new(
+                    allocated_name.span(),
+                    format!("{} is only supported by boxtype = \"leaf\"", attr),
+                ));
+            }
+        }
+    }
+    let is_unknown = |field: &&TupleFieldInfo| matches!(field, TupleFieldInfo::NormalField { options, .. } if options.unknown);
+    if let Some(TupleFieldInfo::NormalField { allocated_name, .. }) =
+        fields.iter().filter(is_unknown).nth(1)
+    {
+        return Err(syn::Error::new(
+            allocated_name.span(),
+            "#[mp4(unknown)] attribute must be one",
+        ));
+    }
+    // the unknown field accepts any box, so it is tried last
     let box_parsers = fields
         .iter()
+        .sorted_by_key(is_unknown)
         .flat_map(|field| match field {
-            TupleFieldInfo::NormalField {
-                ty: _,
-                allocated_name,
-            } => Some({
+            TupleFieldInfo::NormalField { allocated_name, .. } => Some({
                 quote! {
-                    if #allocated_name.acceptable_tag(header.id) {
-                        let value = #allocated_name.read_body(header, &mut reader2).await?;
-                        ::movparse_box::BoxPlaceholder::push(&mut #allocated_name, value)?;
+                    if #allocated_name.acceptable_header(&header) {
+                        #module::BoxPlaceholder::read_child(&mut #allocated_name, header, &mut reader2)#await_?;
                         continue;
                     }
                 }
@@ -607,159 +2410,734 @@ fn gen_code_flakes_for_internal_from_tuple(
             acc.append_all(tokens);
             acc
         });
+    let tag_checks = gen_tag_checks(
+        &fields
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, field)| match field {
+                TupleFieldInfo::NormalField {
+                    decl_ty,
+                    allocated_name,
+                    options,
+                    ..
+                } if !options.unknown => {
+                    Some((format!("${}", idx), decl_ty.as_ref(), allocated_name.span()))
+                }
+                _ => None,
+            })
+            .collect_vec(),
+        generics,
+        &flavor.child_tags,
+    );
     let placeholder_declations = fields
         .iter()
         .flat_map(|field| match field {
-            TupleFieldInfo::NormalField { ty, allocated_name } => Some(quote! {
+            TupleFieldInfo::NormalField {
+                ty, allocated_name, ..
+            } => Some(quote! {
                 let mut #allocated_name = #ty::placeholder();
             }),
             TupleFieldInfo::Header { .. } => None,
         })
-        .fold(TokenStream2::new(), |mut acc, tokens| {
+        .fold(tag_checks, |mut acc, tokens| {
             acc.append_all(tokens);
             acc
         });
     Ok(InternalCodeFlakes {
         placeholder_declations,
         box_parsers,
+        trailer_read: TokenStream2::new(),
     })
 }
 
 fn gen_read_internal_struct_inner(
     name: &TokenStream2,
+    generics: &Generics,
+    attrs: &Vec<Attribute>,
     fields: &Fields,
     span: Span,
+    flavor: &Flavor,
 ) -> Result<TokenStream2, syn::Error> {
+    let Flavor {
+        module,
+        await_,
+        read_header,
+        ..
+    } = flavor;
     let fields_info = parse_fields(fields, fields.span())?;
-    let (placeholders, parsers, struct_return) = match fields_info {
+    let (internal_code_flakes, struct_return) = match fields_info {
+        FieldsInfo::Struct {
+            header_name,
+            fields,
+        } => {
+            let header_name = header_name.ok_or_else(|| {
+                syn::Error::new(
+                    span,
+                    "boxtype = \"internal\" requires one #[movparse(header)]".to_owned(),
+                )
+            })?;
+            let internal_code_flakes =
+                gen_code_flakes_for_internal_from_struct(&fields, generics, flavor)?;
+            let struct_fills =
+                fields
+                    .iter()
+                    .map(child_fill)
+                    .fold(TokenStream2::new(), |mut acc, tokens| {
+                        acc.append_all(tokens);
+                        acc
+                    });
+            let struct_return = quote! {
+                #name {
+                    #struct_fills
+                    #header_name: header,
+                }
+            };
+            (internal_code_flakes, struct_return)
+        }
+        FieldsInfo::Tuple { fields } => {
+            let internal_code_flakes =
+                gen_code_flakes_for_internal_from_tuple(&fields, generics, flavor)?;
+            let struct_fills = fields
+                .iter()
+                .enumerate()
+                .map(|(idx, field)| match field {
+                    TupleFieldInfo::Header { .. } => {
+                        quote! {
+                            header,
+                        }
+                    }
+                    TupleFieldInfo::NormalField { allocated_name, .. } => {
+                        let field_name_str = syn::Lit::Str(syn::LitStr::new(
+                            &format!("${}", idx),
+                            allocated_name.span(),
+                        ));
+                        quote! {
+                            #allocated_name.get(#field_name_str).map_err(|kind| reader.error(kind))?,
+                        }
+                    }
+                })
+                .fold(TokenStream2::new(), |mut acc, tokens| {
+                    acc.append_all(tokens);
+                    acc
+                });
+            let struct_return = quote! {
+                #name(
+                    #struct_fills
+                )
+            };
+            (internal_code_flakes, struct_return)
+        }
+    };
+    let InternalCodeFlakes {
+        box_parsers,
+        placeholder_declations,
+        trailer_read,
+    } = internal_code_flakes;
+    let validate = gen_validate(attrs)?;
+    let derived = quote! {
+        use #module::{BoxContainer, BoxPlaceholder};
+        let mut reader2 = reader.clone();
+        reader2.set_limit(header.body_size() as u64);
+        let mut next_seek = 0;
+        #placeholder_declations
+        // shorter trailers (e.g. the zero terminator of `udta`) are not boxes
+        while reader2.remain() >= 8 {
+            let header = #read_header?;
+            #box_parsers
+            reader2.seek_from_current(header.body_size() as i64)#await_?;
+        }
+        #trailer_read
+        reader.seek_from_current(header.body_size() as i64)#await_?;
+        let value = #struct_return;
+        #validate
+        return Ok(value)
+    };
+    Ok(derived)
+}
+
+fn gen_read_internal_struct(
+    name: &Ident,
+    generics: &Generics,
+    attrs: &Vec<Attribute>,
+    strct: &syn::DataStruct,
+    flavor: &Flavor,
+) -> Result<TokenStream2, syn::Error> {
+    let Flavor {
+        module,
+        impl_attr,
+        read_body_sig,
+        ..
+    } = flavor;
+    let inner = gen_read_internal_struct_inner(
+        &name.to_token_stream(),
+        generics,
+        attrs,
+        &strct.fields,
+        strct.struct_token.span,
+        flavor,
+    )?;
+    let acceptable = BoxTypes::parse(attrs, &strct.fields.span())?.gen_acceptable();
+
+    let (impl_generics, self_ty) = flavor.split_generics(name, generics);
+    let trait_args = flavor.trait_args();
+    let body = flavor.instrument(name, inner);
+    let derived = quote! {
+        #impl_attr
+        impl #impl_generics #module::BoxRead #trait_args for #self_ty {
+
+            #acceptable
+
+            #read_body_sig {
+                #body
+            }
+        }
+    };
+    Ok(derived)
+}
+
+struct WriteArm {
+    header_pattern: TokenStream2,
+    header: TokenStream2,
+    body_pattern: TokenStream2,
+    body_size: TokenStream2,
+    write_stmts: TokenStream2,
+}
+
+fn gen_write_arm(path: &TokenStream2, fields: &Fields, span: Span) -> syn::Result<WriteArm> {
+    let fields_info = parse_fields(fields, span)?;
+    let bit_roles = fields_info.bit_roles()?;
+    let mut bit_roles = bit_roles.iter();
+    let (header_pattern, body_pattern, field_writes) = match fields_info {
         FieldsInfo::Struct {
             header_name,
             fields,
         } => {
             let header_name = header_name.ok_or_else(|| {
-                syn::Error::new(
-                    span,
-                    "boxtype = \"internal\" requires one #[movparse(header)]".to_owned(),
-                )
+                syn::Error::new(span, "BoxWrite requires one #[mp4(header)] attribute")
             })?;
-            let internal_code_flakes = gen_code_flakes_for_internal_from_struct(&fields)?;
-            let struct_fills = fields
+            let bindings = fields
                 .iter()
                 .map(|field| {
-                    let actual_name = &field.field_name;
-                    let allocated_name = &field.allocated_name;
-                    let field_name_str = &field.field_name_str_lit;
+                    let field_name = &field.field_name;
+                    let binding = write_binding(&field.allocated_name, &field.options);
                     quote! {
-                        #actual_name: #allocated_name.get(#field_name_str)?,
+                        #field_name: #binding,
                     }
                 })
                 .fold(TokenStream2::new(), |mut acc, tokens| {
                     acc.append_all(tokens);
                     acc
                 });
-            let struct_return = quote! {
-                Ok(#name {
-                    #struct_fills
-                    #header_name: header,
+            let mut field_writes = fields
+                .iter()
+                .map(|field| {
+                    gen_write_field(
+                        &field.allocated_name,
+                        &field.decl_ty,
+                        &field.options,
+                        bit_roles.next().unwrap(),
+                    )
                 })
-            };
+                .collect_vec();
+            if let Some(order) = fields.iter().find(|field| field.options.order) {
+                let order = &order.allocated_name;
+                let children_at = fields
+                    .iter()
+                    .position(|field| field.options.children)
+                    .unwrap_or(0);
+                let children = (children_at..fields.len())
+                    .filter(|&idx| !fields[idx].options.order && !fields[idx].options.trailer)
+                    .collect_vec();
+                let count = children.len();
+                let (indices, names): (Vec<_>, Vec<_>) = children
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, &field_idx)| (idx, &fields[field_idx].allocated_name))
+                    .unzip();
+                // the children are written in the order they were read, then the ones added since
+                let ordered_write = quote! {
+                    let mut written = [0usize; #count];
+                    for &child in #order.iter() {
+                        match child {
+                            #(
+                                #indices => if written[#indices] < ::movparse_box::ChildWrite::child_count(#names) {
+                                    ::movparse_box::ChildWrite::write_child(#names, written[#indices], writer).await?;
+                                    written[#indices] += 1;
+                                },
+                            )*
+                            _ => (),
+                        }
+                    }
+                    #(
+                        ::movparse_box::ChildWrite::write_children_from(#names, written[#indices], writer).await?;
+                    )*
+                };
+                for &idx in &children {
+                    field_writes[idx].1 = TokenStream2::new();
+                }
+                if let Some(&first) = children.first() {
+                    field_writes[first].1 = ordered_write;
+                }
+            }
             (
-                internal_code_flakes.placeholder_declations,
-                internal_code_flakes.box_parsers,
-                struct_return,
+                quote! {#path { #header_name: header, .. }},
+                quote! {#path { #bindings .. }},
+                field_writes,
             )
         }
         FieldsInfo::Tuple { fields } => {
-            let internal_code_flakes = gen_code_flakes_for_internal_from_tuple(&fields)?;
-            let struct_fills = fields
+            if !fields
                 .iter()
-                .enumerate()
-                .map(|(idx, field)| match field {
-                    TupleFieldInfo::Header { .. } => {
-                        quote! {
-                            header,
-                        }
-                    }
+                .any(|field| matches!(field, TupleFieldInfo::Header { .. }))
+            {
+                return Err(syn::Error::new(
+                    span,
+                    "BoxWrite requires one #[mp4(header)] attribute",
+                ));
+            }
+            let header_bindings = fields
+                .iter()
+                .map(|field| match field {
+                    TupleFieldInfo::Header { .. } => quote! {header,},
+                    TupleFieldInfo::NormalField { .. } => quote! {_,},
+                })
+                .fold(TokenStream2::new(), |mut acc, tokens| {
+                    acc.append_all(tokens);
+                    acc
+                });
+            let body_bindings = fields
+                .iter()
+                .map(|field| match field {
+                    TupleFieldInfo::Header { .. } => quote! {_,},
                     TupleFieldInfo::NormalField {
-                        ty: _,
                         allocated_name,
+                        options,
+                        ..
                     } => {
-                        let field_name_str = syn::Lit::Str(syn::LitStr::new(
-                            &format!("${}", idx),
-                            allocated_name.span(),
-                        ));
-                        quote! {
-                            #allocated_name.get(#field_name_str)?,
-                        }
+                        let binding = write_binding(allocated_name, options);
+                        quote! {#binding,}
                     }
                 })
                 .fold(TokenStream2::new(), |mut acc, tokens| {
                     acc.append_all(tokens);
                     acc
                 });
-            let struct_return = quote! {
-                Ok(#name(
-                    #struct_fills
-                ))
-            };
             (
-                internal_code_flakes.placeholder_declations,
-                internal_code_flakes.box_parsers,
-                struct_return,
+                quote! {#path(#header_bindings)},
+                quote! {#path(#body_bindings)},
+                fields
+                    .iter()
+                    .flat_map(|field| match field {
+                        TupleFieldInfo::Header { .. } => None,
+                        TupleFieldInfo::NormalField {
+                            allocated_name,
+                            decl_ty,
+                            options,
+                            ..
+                        } => Some(gen_write_field(
+                            allocated_name,
+                            decl_ty,
+                            options,
+                            bit_roles.next().unwrap(),
+                        )),
+                    })
+                    .collect_vec(),
             )
         }
     };
-    let derived = quote! {
-        use ::movparse_box::{BoxContainer, BoxPlaceholder};
-        let mut reader2 = reader.clone();
-        reader2.set_limit(header.body_size() as u64);
-        let mut next_seek = 0;
-        #placeholders
-        while reader2.remain() > 0 {
-            let header = ::movparse_box::BoxHeader::read(&mut reader2).await?;
-            #parsers
-            reader2.seek_from_current(header.body_size() as i64).await?;
-        }
-        reader.seek_from_current(header.body_size() as i64).await?;
-        return #struct_return
-    };
-    Ok(derived)
+    let body_size = field_writes
+        .iter()
+        .map(|(size, _)| {
+            quote! {
+                + #size
+            }
+        })
+        .fold(quote! {0}, |mut acc, tokens| {
+            acc.append_all(tokens);
+            acc
+        });
+    let write_stmts = field_writes.iter().map(|(_, write)| write.clone()).fold(
+        TokenStream2::new(),
+        |mut acc, tokens| {
+            acc.append_all(tokens);
+            acc
+        },
+    );
+    Ok(WriteArm {
+        header_pattern,
+        header: quote! {*header},
+        body_pattern,
+        body_size,
+        write_stmts,
+    })
 }
 
-fn gen_read_internal_struct(
+fn gen_write(
     name: &Ident,
-    attrs: &Vec<Attribute>,
-    strct: &syn::DataStruct,
+    generics: &Generics,
+    data: &syn::Data,
 ) -> Result<TokenStream, syn::Error> {
-    let inner = gen_read_internal_struct_inner(
-        &name.to_token_stream(),
-        &strct.fields,
-        strct.struct_token.span,
-    )?;
-    let acceptable_tag = acceptable_tag(attrs, &strct.fields.span())?;
+    let arms = match data {
+        syn::Data::Struct(strct) => vec![gen_write_arm(
+            &quote! {Self},
+            &strct.fields,
+            strct.struct_token.span,
+        )?],
+        syn::Data::Enum(enm) => {
+            let fallback = fallback_variant(enm)?;
+            enm.variants
+                .iter()
+                .map(|variant| {
+                    let name = &variant.ident;
+                    if fallback.is_some_and(|fallback| fallback.ident == variant.ident) {
+                        // the box kept by the fallback variant writes itself
+                        return Ok(WriteArm {
+                            header_pattern: quote! {Self::#name(inner)},
+                            header: quote! {::movparse_box::BoxWrite::header(inner)},
+                            body_pattern: quote! {Self::#name(inner)},
+                            body_size: quote! {::movparse_box::BoxWrite::body_size(inner)},
+                            write_stmts: quote! {
+                                ::movparse_box::BoxWrite::write_body(inner, writer).await?;
+                            },
+                        });
+                    }
+                    gen_write_arm(&quote! {Self::#name}, &variant.fields, variant.span())
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        }
+        syn::Data::Union(unn) => {
+            return Err(syn::Error::new(
+                unn.union_token.span,
+                "union type is not supported",
+            ))
+        }
+    };
+    let header_arms = arms
+        .iter()
+        .map(|arm| {
+            let pattern = &arm.header_pattern;
+            let header = &arm.header;
+            quote! {
+                #pattern => #header,
+            }
+        })
+        .fold(TokenStream2::new(), |mut acc, tokens| {
+            acc.append_all(tokens);
+            acc
+        });
+    let body_size_arms = arms
+        .iter()
+        .map(|arm| {
+            let pattern = &arm.body_pattern;
+            let body_size = &arm.body_size;
+            quote! {
+                #pattern => #body_size,
+            }
+        })
+        .fold(TokenStream2::new(), |mut acc, tokens| {
+            acc.append_all(tokens);
+            acc
+        });
+    let write_arms = arms
+        .iter()
+        .map(|arm| {
+            let pattern = &arm.body_pattern;
+            let write_stmts = &arm.write_stmts;
+            quote! {
+                #pattern => {
+                    #write_stmts
+                }
+            }
+        })
+        .fold(TokenStream2::new(), |mut acc, tokens| {
+            acc.append_all(tokens);
+            acc
+        });
 
+    let (impl_generics, self_ty) = split_generics(name, generics, &send_sync());
     let derived = quote! {
         #[::async_trait::async_trait]
-        impl ::movparse_box::BoxRead for #name {
+        impl #impl_generics ::movparse_box::BoxWrite for #self_ty {
+            fn header(&self) -> ::movparse_box::BoxHeader {
+                match self {
+                    #header_arms
+                }
+            }
 
-            fn acceptable_tag(tag: [u8;4]) -> bool {
-                #acceptable_tag.contains(&tag)
+            fn body_size(&self) -> u64 {
+                match self {
+                    #body_size_arms
+                }
             }
 
-            async fn read_body<R: ::tokio::io::AsyncRead + ::tokio::io::AsyncSeek + ::std::marker::Unpin + ::std::marker::Send>(
-                header: ::movparse_box::BoxHeader,
-                reader: &mut ::movparse_box::Reader<R>,
-            ) -> std::io::Result<Self> {
-                #inner
+            async fn write_body<W: ::tokio::io::AsyncWrite + ::std::marker::Unpin + ::std::marker::Send>(
+                &self,
+                writer: &mut W,
+            ) -> std::io::Result<()> {
+                match self {
+                    #write_arms
+                }
+                Ok(())
             }
         }
     };
     Ok(derived.into())
 }
 
+/// Integer type of `#[mp4(repr = "u32")]` on C-like enums
+fn read_repr(attrs: &Vec<Attribute>, span: Span) -> Result<Ident, syn::Error> {
+    for attr in parse_mp4_attrs(attrs)? {
+        if let Mp4Attr::WithValue(path, value) = attr {
+            if path.is_ident("repr") {
+                return match &value {
+                    syn::Lit::Str(s)
+                        if ["u8", "u16", "u32", "u64"].contains(&s.value().as_str()) =>
+                    {
+                        Ok(Ident::new(&s.value(), s.span()))
+                    }
+                    _ => Err(syn::Error::new_spanned(
+                        value.to_token_stream(),
+                        "mp4(repr = <\"u8\" | \"u16\" | \"u32\" | \"u64\">)",
+                    )),
+                };
+            }
+        }
+    }
+    Err(syn::Error::new(
+        span,
+        "AttrRead for enums requires mp4(repr = <integer type>)",
+    ))
+}
+
+/// Unit variants of a C-like enum
+fn unit_variants(enm: &syn::DataEnum) -> Result<Vec<&Ident>, syn::Error> {
+    enm.variants
+        .iter()
+        .map(|variant| match variant.fields {
+            Fields::Unit => Ok(&variant.ident),
+            _ => Err(syn::Error::new(
+                variant.span(),
+                "AttrRead and AttrWrite support only C-like enums",
+            )),
+        })
+        .collect()
+}
+
+fn gen_read_attr(
+    name: &Ident,
+    generics: &Generics,
+    attrs: &Vec<Attribute>,
+    data: &syn::Data,
+    flavor: &Flavor,
+) -> Result<TokenStream2, syn::Error> {
+    let Flavor {
+        module,
+        await_,
+        impl_attr,
+        read_attr_sig,
+        ..
+    } = flavor;
+    let body = match data {
+        syn::Data::Struct(strct) => {
+            let span = strct.struct_token.span;
+            let fields_info = parse_fields(&strct.fields, span)?;
+            let has_header = match &fields_info {
+                FieldsInfo::Struct { header_name, .. } => header_name.is_some(),
+                FieldsInfo::Tuple { fields } => fields
+                    .iter()
+                    .any(|field| matches!(field, TupleFieldInfo::Header { .. })),
+            };
+            if has_header {
+                return Err(syn::Error::new(
+                    span,
+                    "#[mp4(header)] is unrecognized option for AttrRead",
+                ));
+            }
+            let (assign_stmts, struct_return) = gen_read_fields(
+                &quote! {Self},
+                attrs,
+                fields_info,
+                TokenStream2::new(),
+                false,
+                span,
+                flavor,
+            )?;
+            quote! {
+                let reader2 = reader;
+                #assign_stmts
+                Ok(#struct_return)
+            }
+        }
+        syn::Data::Enum(enm) => {
+            let repr = read_repr(attrs, enm.enum_token.span)?;
+            let variants = unit_variants(enm)?;
+            quote! {
+                let offset = reader.pos;
+                let value: #repr = #module::AttrRead::read_attr(reader)#await_?;
+                #(
+                    if value == Self::#variants as #repr {
+                        return Ok(Self::#variants);
+                    }
+                )*
+                Err(::movparse_box::Error::new(
+                    ::movparse_box::ErrorKind::UnknownValue { value: value as u64 },
+                    reader.path.clone(),
+                    offset,
+                ))
+            }
+        }
+        syn::Data::Union(unn) => {
+            return Err(syn::Error::new(
+                unn.union_token.span,
+                "union type is not supported",
+            ))
+        }
+    };
+    let (impl_generics, self_ty) = flavor.split_generics(name, generics);
+    let trait_args = flavor.trait_args();
+    Ok(quote! {
+        #impl_attr
+        impl #impl_generics #module::AttrRead #trait_args for #self_ty {
+            #read_attr_sig {
+                #body
+            }
+        }
+    })
+}
+
+fn gen_write_attr(
+    name: &Ident,
+    generics: &Generics,
+    attrs: &Vec<Attribute>,
+    data: &syn::Data,
+) -> Result<TokenStream2, syn::Error> {
+    let (attr_size, write_stmts) = match data {
+        syn::Data::Struct(strct) => {
+            let span = strct.struct_token.span;
+            let fields_info = parse_fields(&strct.fields, span)?;
+            let bit_roles = fields_info.bit_roles()?;
+            let mut bit_roles = bit_roles.iter();
+            let (pattern, field_writes) = match fields_info {
+                FieldsInfo::Struct {
+                    header_name: None,
+                    fields,
+                } => {
+                    let field_names = fields.iter().map(|field| &field.field_name);
+                    let bindings = fields
+                        .iter()
+                        .map(|field| write_binding(&field.allocated_name, &field.options));
+                    (
+                        quote! {Self { #(#field_names: #bindings),* }},
+                        fields
+                            .iter()
+                            .map(|field| {
+                                gen_write_field(
+                                    &field.allocated_name,
+                                    &field.decl_ty,
+                                    &field.options,
+                                    bit_roles.next().unwrap(),
+                                )
+                            })
+                            .collect_vec(),
+                    )
+                }
+                FieldsInfo::Tuple { fields }
+                    if fields
+                        .iter()
+                        .all(|field| matches!(field, TupleFieldInfo::NormalField { .. })) =>
+                {
+                    let bindings = fields.iter().flat_map(|field| match field {
+                        TupleFieldInfo::NormalField {
+                            allocated_name,
+                            options,
+                            ..
+                        } => Some(write_binding(allocated_name, options)),
+                        TupleFieldInfo::Header { .. } => None,
+                    });
+                    let field_writes = fields
+                        .iter()
+                        .flat_map(|field| match field {
+                            TupleFieldInfo::NormalField {
+                                allocated_name,
+                                decl_ty,
+                                options,
+                                ..
+                            } => Some(gen_write_field(
+                                allocated_name,
+                                decl_ty,
+                                options,
+                                bit_roles.next().unwrap(),
+                            )),
+                            TupleFieldInfo::Header { .. } => None,
+                        })
+                        .collect_vec();
+                    (quote! {Self(#(#bindings),*)}, field_writes)
+                }
+                _ => {
+                    return Err(syn::Error::new(
+                        span,
+                        "#[mp4(header)] is unrecognized option for AttrWrite",
+                    ))
+                }
+            };
+            let (sizes, writes): (Vec<_>, Vec<_>) = field_writes.into_iter().unzip();
+            (
+                quote! {
+                    let #pattern = self;
+                    0 #(+ #sizes)*
+                },
+                quote! {
+                    let #pattern = self;
+                    #(#writes)*
+                },
+            )
+        }
+        syn::Data::Enum(enm) => {
+            let repr = read_repr(attrs, enm.enum_token.span)?;
+            let variants = unit_variants(enm)?;
+            (
+                quote! {
+                    ::std::mem::size_of::<#repr>() as u64
+                },
+                quote! {
+                    let value: #repr = match self {
+                        #(Self::#variants => Self::#variants as #repr,)*
+                    };
+                    ::movparse_box::AttrWrite::write_attr(&value, writer).await?;
+                },
+            )
+        }
+        syn::Data::Union(unn) => {
+            return Err(syn::Error::new(
+                unn.union_token.span,
+                "union type is not supported",
+            ))
+        }
+    };
+    let (impl_generics, self_ty) = split_generics(name, generics, &send_sync());
+    Ok(quote! {
+        #[::async_trait::async_trait]
+        impl #impl_generics ::movparse_box::AttrWrite for #self_ty {
+            fn attr_size(&self) -> u64 {
+                #attr_size
+            }
+
+            async fn write_attr<W: ::tokio::io::AsyncWrite + ::std::marker::Unpin + ::std::marker::Send>(
+                &self,
+                writer: &mut W,
+            ) -> std::io::Result<()> {
+                #write_stmts
+                Ok(())
+            }
+        }
+    })
+}
+
 enum Mp4BoxType {
     Leaf,
     Internal,
+    /// Leaf fields followed by child boxes
+    Hybrid,
 }
 
 enum Mp4Attr {
@@ -792,6 +3170,28 @@ fn parse_mp4_attrs(attrs: &Vec<Attribute>) -> Result<Vec<Mp4Attr>, syn::Error> {
     Ok(mp4_attrs)
 }
 
+/// Whether the type is marked `#[mp4(borrow)]`, deriving only the `movparse_box::slice` traits
+fn borrows(attrs: &Vec<Attribute>) -> Result<bool, syn::Error> {
+    Ok(parse_mp4_attrs(attrs)?
+        .iter()
+        .any(|attr| matches!(attr, Mp4Attr::Name(name) if name.is_ident("borrow"))))
+}
+
+/// `AttrRead` of a box marked `#[mp4(borrow)]`, which the blanket impl over the blocking traits does not cover
+fn gen_slice_box_attr_read(name: &Ident, generics: &Generics) -> TokenStream2 {
+    let flavor = Flavor::slice();
+    let (impl_generics, self_ty) = flavor.split_generics(name, generics);
+    let trait_args = flavor.trait_args();
+    let read_attr_sig = &flavor.read_attr_sig;
+    quote! {
+        impl #impl_generics ::movparse_box::slice::AttrRead #trait_args for #self_ty {
+            #read_attr_sig {
+                ::movparse_box::slice::read_box(reader)
+            }
+        }
+    }
+}
+
 fn read_mp4_box_type<T: ToTokens>(
     attrs: &Vec<Attribute>,
     tokens: T,
@@ -801,14 +3201,15 @@ fn read_mp4_box_type<T: ToTokens>(
         if let Mp4Attr::WithValue(tag, lit) = attr {
             if tag.is_ident("boxtype") {
                 let syn::Lit::Str(s) = &lit else {
-                    return Err(syn::Error::new_spanned(lit.to_token_stream(), "mp4(boxtype = <\"leaf\" | \"internal\">)"))
+                    return Err(syn::Error::new_spanned(lit.to_token_stream(), "mp4(boxtype = <\"leaf\" | \"internal\" | \"hybrid\">)"))
                 };
                 return match s.value().as_str() {
                     "leaf" => Ok(Mp4BoxType::Leaf),
                     "internal" => Ok(Mp4BoxType::Internal),
+                    "hybrid" => Ok(Mp4BoxType::Hybrid),
                     _ => Err(syn::Error::new_spanned(
                         lit.to_token_stream(),
-                        "mp4(boxtype = <\"leaf\" | \"internal\">)",
+                        "mp4(boxtype = <\"leaf\" | \"internal\" | \"hybrid\">)",
                     )),
                 };
             }
@@ -828,19 +3229,73 @@ pub fn derive_mp4_box(input: TokenStream) -> TokenStream {
         Ok(v) => v,
         Err(e) => return e.to_compile_error().into(),
     };
-    let result = match (&input.data, box_type) {
-        (syn::Data::Struct(v), Mp4BoxType::Leaf) => {
-            gen_read_leaf_struct(&input.ident, &input.attrs, v)
-        }
-        (syn::Data::Struct(v), Mp4BoxType::Internal) => {
-            gen_read_internal_struct(&input.ident, &input.attrs, v)
-        }
-        (syn::Data::Enum(v), Mp4BoxType::Leaf) => gen_read_leaf_enum(&input.ident, v),
-        (syn::Data::Enum(v), Mp4BoxType::Internal) => gen_read_internal_enum(&input.ident, v),
-        (_, _) => panic!("union type is not supported"),
+    let flavors = match Flavor::of(&input.attrs) {
+        Ok(v) => v,
+        Err(e) => return e.to_compile_error().into(),
     };
+    let result = flavors
+        .iter()
+        .map(|flavor| match (&input.data, &box_type) {
+            (syn::Data::Struct(v), Mp4BoxType::Leaf) => gen_read_leaf_struct(
+                &input.ident,
+                &input.generics,
+                &input.attrs,
+                v,
+                gen_read_leaf_struct_inner,
+                flavor,
+            ),
+            (syn::Data::Struct(v), Mp4BoxType::Hybrid) => gen_read_leaf_struct(
+                &input.ident,
+                &input.generics,
+                &input.attrs,
+                v,
+                gen_read_hybrid_struct_inner,
+                flavor,
+            ),
+            (syn::Data::Struct(v), Mp4BoxType::Internal) => {
+                gen_read_internal_struct(&input.ident, &input.generics, &input.attrs, v, flavor)
+            }
+            (syn::Data::Enum(v), Mp4BoxType::Leaf) => gen_read_enum(
+                &input.ident,
+                &input.generics,
+                v,
+                gen_read_leaf_struct_inner,
+                flavor,
+            ),
+            (syn::Data::Enum(v), Mp4BoxType::Hybrid) => gen_read_enum(
+                &input.ident,
+                &input.generics,
+                v,
+                gen_read_hybrid_struct_inner,
+                flavor,
+            ),
+            (syn::Data::Enum(v), Mp4BoxType::Internal) => gen_read_enum(
+                &input.ident,
+                &input.generics,
+                v,
+                gen_read_internal_struct_inner,
+                flavor,
+            ),
+            (_, _) => panic!("union type is not supported"),
+        })
+        .collect::<Result<TokenStream2, _>>()
+        .and_then(|mut generated| {
+            let hybrid = matches!(box_type, Mp4BoxType::Hybrid);
+            if !matches!(box_type, Mp4BoxType::Leaf) {
+                generated.append_all(gen_tag_check_items(
+                    &input.generics,
+                    &input.data,
+                    hybrid,
+                    &flavors[0].child_tags,
+                )?);
+            }
+            if flavors[0].source_lifetime.is_some() {
+                generated.append_all(gen_slice_box_attr_read(&input.ident, &input.generics));
+            }
+            Ok(generated)
+        });
     match result {
-        Ok(generated) => generated,
+        Ok(generated) => generated.into(),
         Err(e) => e.to_compile_error().into(),
     }
 }
@@ -848,13 +3303,79 @@ pub fn derive_mp4_box(input: TokenStream) -> TokenStream {
 #[proc_macro_derive(RootRead, attributes(mp4))]
 pub fn derive_root_read(input: TokenStream) -> TokenStream {
     let input = &parse_macro_input!(input as DeriveInput);
-
-    let result = match &input.data {
-        syn::Data::Struct(v) => derive_mp4_root_read_for_struct(&input.ident, v),
-        _ => Err(syn::Error::new_spanned(&input.ident, "Must be struct Type")),
+    let flavors = match Flavor::of(&input.attrs) {
+        Ok(v) => v,
+        Err(e) => return e.to_compile_error().into(),
     };
+    let result = flavors
+        .iter()
+        .map(|flavor| match &input.data {
+            syn::Data::Struct(v) => {
+                derive_mp4_root_read_for_struct(&input.ident, &input.generics, v, flavor)
+            }
+            syn::Data::Enum(v) => {
+                derive_mp4_root_read_for_enum(&input.ident, &input.generics, v, flavor)
+            }
+            syn::Data::Union(_) => Err(syn::Error::new_spanned(
+                &input.ident,
+                "Must be struct or enum Type",
+            )),
+        })
+        .collect::<Result<TokenStream2, _>>()
+        .and_then(|mut generated| {
+            generated.append_all(gen_tag_check_items(
+                &input.generics,
+                &input.data,
+                false,
+                &flavors[0].child_tags,
+            )?);
+            Ok(generated)
+        });
     match result {
+        Ok(generated) => generated.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+#[proc_macro_derive(BoxWrite, attributes(mp4))]
+pub fn derive_box_write(input: TokenStream) -> TokenStream {
+    let input = &parse_macro_input!(input as DeriveInput);
+    match gen_write(&input.ident, &input.generics, &input.data) {
         Ok(generated) => generated,
         Err(e) => e.to_compile_error().into(),
     }
 }
+
+#[proc_macro_derive(AttrRead, attributes(mp4))]
+pub fn derive_attr_read(input: TokenStream) -> TokenStream {
+    let input = &parse_macro_input!(input as DeriveInput);
+    let flavors = match Flavor::of(&input.attrs) {
+        Ok(v) => v,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let result = flavors
+        .iter()
+        .map(|flavor| {
+            gen_read_attr(
+                &input.ident,
+                &input.generics,
+                &input.attrs,
+                &input.data,
+                flavor,
+            )
+        })
+        .collect::<Result<TokenStream2, _>>();
+    match result {
+        Ok(generated) => generated.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+#[proc_macro_derive(AttrWrite, attributes(mp4))]
+pub fn derive_attr_write(input: TokenStream) -> TokenStream {
+    let input = &parse_macro_input!(input as DeriveInput);
+    match gen_write_attr(&input.ident, &input.generics, &input.attrs, &input.data) {
+        Ok(generated) => generated.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
@@ -1,7 +1,7 @@
-use movparse_box::BoxHeader;
-use movparse_derive::BoxRead;
+use movparse_box::{AttrWrite, BoxHeader};
+use movparse_derive::{AttrRead, AttrWrite, BoxRead, BoxWrite, RootRead};
 
-#[derive(BoxRead, Debug, PartialEq, Eq)]
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
 #[mp4(boxtype = "leaf")]
 #[mp4(tag = "ftyp")]
 struct Ftyp {
@@ -12,7 +12,7 @@ struct Ftyp {
     pub compatible_brands: Vec<[u8; 4]>,
 }
 
-#[derive(BoxRead, Debug, PartialEq, Eq)]
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
 #[mp4(boxtype = "leaf")]
 #[mp4(tag = "data")]
 struct Data {
@@ -20,7 +20,7 @@ struct Data {
     header: BoxHeader,
 }
 
-#[derive(BoxRead, Debug, PartialEq, Eq)]
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
 #[mp4(boxtype = "leaf")]
 enum TestLeaf {
     #[mp4(tag = "foo ")]
@@ -43,6 +43,413 @@ struct Test {
     data: Vec<Data>,
 }
 
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
+#[mp4(boxtype = "internal")]
+#[mp4(tag = "pair")]
+struct Pair {
+    #[mp4(header)]
+    header: BoxHeader,
+    ftyp: Ftyp,
+    leaves: Vec<TestLeaf>,
+}
+
+#[derive(BoxRead, Debug)]
+#[mp4(boxtype = "internal")]
+#[mp4(tag = "pair")]
+struct LazyPair {
+    #[mp4(header)]
+    header: BoxHeader,
+    ftyp: movparse_box::Lazy<Ftyp>,
+    leaves: Vec<movparse_box::Lazy<TestLeaf>>,
+}
+
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
+#[mp4(boxtype = "leaf")]
+#[mp4(uuid = "6d1d9b05-42d5-44e6-80e2-141daff757b2")]
+struct Tfxd {
+    #[mp4(header)]
+    header: BoxHeader,
+    time: u32,
+}
+
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
+#[mp4(boxtype = "leaf")]
+#[mp4(uuid = "d4807ef2-ca39-4695-8e54-26cb9e46a79f")]
+struct Tfrf {
+    #[mp4(header)]
+    header: BoxHeader,
+    count: u8,
+}
+
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
+#[mp4(boxtype = "internal")]
+#[mp4(tag = "traf")]
+struct Traf {
+    #[mp4(header)]
+    header: BoxHeader,
+    tfxd: Tfxd,
+    tfrf: Option<Tfrf>,
+}
+
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
+#[mp4(boxtype = "leaf")]
+#[mp4(tag = "mdhd")]
+#[mp4(versions = "0")]
+struct Mdhd {
+    #[mp4(header)]
+    header: BoxHeader,
+    #[mp4(fullbox)]
+    fullbox: movparse_box::FullBoxHeader,
+    timescale: u32,
+}
+
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
+#[mp4(boxtype = "leaf")]
+#[mp4(tag = "tfdt")]
+struct Tfdt {
+    #[mp4(header)]
+    header: BoxHeader,
+    #[mp4(fullbox)]
+    fullbox: movparse_box::FullBoxHeader,
+    #[mp4(if_version = 1)]
+    base_media_decode_time: movparse_box::Either<u64, u32>,
+    #[mp4(cond = "flags.contains(0x1)")]
+    sample_count: Option<u16>,
+    #[mp4(cond = "sample_count.is_some_and(|count| count > 0)")]
+    first_sample_flags: Option<u32>,
+}
+
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
+#[mp4(boxtype = "leaf")]
+#[mp4(tag = "stco")]
+struct Stco {
+    #[mp4(header)]
+    header: BoxHeader,
+    entry_count: u32,
+    #[mp4(count = "entry_count")]
+    chunk_offsets: Vec<u32>,
+}
+
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
+#[mp4(boxtype = "leaf")]
+#[mp4(tag = "hdlr")]
+struct Hdlr {
+    #[mp4(header)]
+    header: BoxHeader,
+    handler_type: [u8; 4],
+}
+
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
+#[mp4(boxtype = "hybrid")]
+#[mp4(tag = "meta")]
+struct Meta {
+    #[mp4(header)]
+    header: BoxHeader,
+    #[mp4(fullbox)]
+    fullbox: movparse_box::FullBoxHeader,
+    #[mp4(children)]
+    hdlr: Hdlr,
+    data: Vec<Data>,
+}
+
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
+#[mp4(boxtype = "internal")]
+#[mp4(tag = "udta")]
+struct Udta {
+    #[mp4(header)]
+    header: BoxHeader,
+    #[mp4(unknown)]
+    others: Vec<movparse_box::RawBox>,
+    hdlr: Option<Hdlr>,
+    #[mp4(order)]
+    order: Vec<usize>,
+    #[mp4(trailer)]
+    trailer: movparse_box::Bytes,
+}
+
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
+#[mp4(boxtype = "internal")]
+enum Container {
+    #[mp4(tag = "meta")]
+    Meta {
+        #[mp4(header)]
+        header: BoxHeader,
+        hdlr: Hdlr,
+    },
+    #[mp4(tag = "dinf")]
+    Dinf(#[mp4(header)] BoxHeader, Vec<Data>),
+    #[mp4(unknown)]
+    Unknown(movparse_box::RawBox),
+}
+
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
+#[mp4(boxtype = "internal")]
+#[mp4(tag = "trak")]
+struct GenericTrak<Extra>
+where
+    Extra: movparse_box::BoxRead + movparse_box::blocking::BoxRead + movparse_box::BoxWrite,
+{
+    #[mp4(header)]
+    header: BoxHeader,
+    hdlr: Hdlr,
+    extra: Option<Extra>,
+}
+
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
+#[mp4(boxtype = "leaf")]
+#[mp4(tag = "valu")]
+struct Value<'a, T: movparse_box::AttrRead + movparse_box::blocking::AttrRead + AttrWrite> {
+    #[mp4(header)]
+    header: BoxHeader,
+    value: T,
+    marker: std::marker::PhantomData<&'a ()>,
+}
+
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
+#[mp4(boxtype = "leaf")]
+#[mp4(tag = "pasp")]
+struct Pasp {
+    #[mp4(header)]
+    header: BoxHeader,
+    h_spacing: u32,
+    v_spacing: u32,
+}
+
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
+#[mp4(boxtype = "hybrid")]
+enum SampleEntry {
+    #[mp4(tag = "avc1")]
+    Avc1 {
+        #[mp4(header)]
+        header: BoxHeader,
+        data_reference_index: u16,
+        #[mp4(children)]
+        pasp: Option<Pasp>,
+        btrt: Option<Btrt>,
+        #[mp4(order)]
+        order: Vec<usize>,
+        #[mp4(trailer)]
+        trailer: movparse_box::Bytes,
+    },
+    #[mp4(tag = "mp4a")]
+    Mp4a {
+        #[mp4(header)]
+        header: BoxHeader,
+        data_reference_index: u16,
+    },
+}
+
+#[derive(RootRead, Debug, PartialEq, Eq)]
+enum Layout {
+    #[mp4(brand = "heic")]
+    #[mp4(brand = "mif1")]
+    Heif {
+        ftyp: Ftyp,
+        meta: Meta,
+    },
+    #[mp4(has = "data")]
+    Fragmented {
+        ftyp: Option<Ftyp>,
+        data: Vec<Data>,
+    },
+    Legacy(Pair),
+}
+
+#[derive(RootRead, Debug)]
+enum IsoOnly {
+    #[mp4(brand = "isom")]
+    Iso { ftyp: Ftyp },
+}
+
+#[derive(BoxRead, Debug, PartialEq, Eq)]
+#[mp4(boxtype = "leaf")]
+#[mp4(tag = "rang")]
+#[mp4(validate = "Range::validate")]
+struct Range {
+    #[mp4(header)]
+    header: BoxHeader,
+    #[mp4(magic = b"rng1")]
+    magic: [u8; 4],
+    #[mp4(assert = "*timescale != 0")]
+    timescale: u32,
+    #[mp4(magic = 0)]
+    reserved: u16,
+    first: u32,
+    last: u32,
+}
+
+impl Range {
+    fn validate(&self) -> Result<(), movparse_box::ErrorKind> {
+        if self.first > self.last {
+            return Err(movparse_box::ErrorKind::Invalid {
+                reason: format!("{} > {}", self.first, self.last),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// String prefixed by its length in one byte
+mod pascal {
+    use movparse_box::{blocking, AttrRead, Error, Reader};
+    use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, AsyncWriteExt};
+
+    fn decode(bytes: Vec<u8>) -> String {
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    pub async fn read<R: AsyncRead + AsyncSeek + Unpin + Send>(
+        reader: &mut Reader<R>,
+    ) -> Result<String, Error> {
+        let len: u8 = AttrRead::read_attr(reader).await?;
+        let mut bytes = vec![0; len as usize];
+        reader.read_exact(&mut bytes).await?;
+        Ok(decode(bytes))
+    }
+
+    pub fn read_blocking<R: blocking::Source>(
+        reader: &mut blocking::Reader<R>,
+    ) -> Result<String, Error> {
+        let len: u8 = blocking::AttrRead::read_attr(reader)?;
+        Ok(decode(reader.read_bytes(len as usize)?.to_vec()))
+    }
+
+    pub fn size(value: &str) -> u64 {
+        1 + value.len() as u64
+    }
+
+    pub async fn write<W: AsyncWrite + Unpin + Send>(
+        value: &str,
+        writer: &mut W,
+    ) -> std::io::Result<()> {
+        writer.write_u8(value.len() as u8).await?;
+        writer.write_all(value.as_bytes()).await
+    }
+}
+
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
+#[mp4(boxtype = "leaf")]
+#[mp4(tag = "name")]
+struct Name {
+    #[mp4(header)]
+    header: BoxHeader,
+    #[mp4(with = "pascal")]
+    name: String,
+    #[mp4(skip = 3)]
+    #[mp4(reserved)]
+    flags: u8,
+    width: u16,
+    #[mp4(default = "*width as u32 * 2")]
+    double_width: u32,
+    #[mp4(default)]
+    cache: Vec<u8>,
+}
+
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
+#[mp4(boxtype = "leaf")]
+#[mp4(tag = "smhd")]
+struct Smhd {
+    #[mp4(header)]
+    header: BoxHeader,
+    balance: u16,
+    #[mp4(trailer)]
+    trailer: movparse_box::Bytes,
+}
+
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
+#[mp4(boxtype = "leaf")]
+#[mp4(tag = "btrt")]
+struct Btrt {
+    #[mp4(header)]
+    header: BoxHeader,
+    buffer_size: u32,
+    max_bitrate: Option<u32>,
+    avg_bitrate: Option<u32>,
+}
+
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
+#[mp4(boxtype = "leaf")]
+#[mp4(tag = "avcC")]
+struct AvcC {
+    #[mp4(header)]
+    header: BoxHeader,
+    configuration_version: u8,
+    #[mp4(bits = 6)]
+    #[mp4(magic = 0x3f)]
+    reserved: u8,
+    #[mp4(bits = 2)]
+    length_size_minus_one: u8,
+    #[mp4(bits = 3)]
+    sps_reserved: u8,
+    #[mp4(bits = 5)]
+    sps_count: u8,
+    #[mp4(bits = 1)]
+    flag: bool,
+    #[mp4(bits = 15)]
+    wide: u16,
+    tail: u8,
+}
+
+/// iTunes metadata atoms, whose tags start with 0xA9
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
+#[mp4(boxtype = "leaf")]
+enum Metadata {
+    #[mp4(tag = "©nam")]
+    Name(#[mp4(header)] BoxHeader, u8),
+    #[mp4(tag = b"\xA9day")]
+    Day(#[mp4(header)] BoxHeader, u8),
+    #[mp4(tag = 0xA9415254)]
+    Artist(#[mp4(header)] BoxHeader, u8),
+}
+
+#[derive(AttrRead, AttrWrite, Debug, PartialEq, Eq)]
+struct Entry {
+    count: u32,
+    delta: u16,
+}
+
+#[derive(AttrRead, AttrWrite, Clone, Copy, Debug, PartialEq, Eq)]
+#[mp4(repr = "u8")]
+enum Kind {
+    Video = 1,
+    Audio = 2,
+}
+
+#[derive(AttrRead, AttrWrite, Debug, PartialEq, Eq)]
+struct KindEntry(Kind, Entry);
+
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
+#[mp4(boxtype = "leaf")]
+#[mp4(tag = "free")]
+#[mp4(borrow)]
+struct FreeRef<'a> {
+    #[mp4(header)]
+    header: BoxHeader,
+    kind: u32,
+    data: &'a [u8],
+}
+
+#[derive(BoxRead, BoxWrite, Debug, PartialEq, Eq)]
+#[mp4(boxtype = "internal")]
+#[mp4(tag = "pair")]
+#[mp4(borrow)]
+struct PairRef<'a> {
+    #[mp4(header)]
+    header: BoxHeader,
+    ftyp: Ftyp,
+    frees: Vec<FreeRef<'a>>,
+    #[mp4(unknown)]
+    others: Vec<movparse_box::RawBox>,
+    #[mp4(order)]
+    order: Vec<usize>,
+}
+
+#[derive(RootRead, Debug, PartialEq, Eq)]
+#[mp4(borrow)]
+struct FileRef<'a> {
+    pair: PairRef<'a>,
+}
+
 #[cfg(test)]
 mod test {
     use movparse_box::*;
@@ -53,6 +460,7 @@ mod test {
     use super::*;
 
     #[tokio::test]
+    #[allow(clippy::byte_char_slices)]
     async fn test() {
         let mut ftyp = Vec::new();
         let major_brand = &[b'r', b'u', b's', b't'];
@@ -93,7 +501,9 @@ mod test {
             Ftyp {
                 header: BoxHeader {
                     id: [b'f', b't', b'y', b'p'],
-                    size: 24
+                    size: 24,
+                    largesize: false,
+                    usertype: None,
                 },
                 major_brand: [b'r', b'u', b's', b't'],
                 minor_version: [b'm', b'p', b'4', b'r'],
@@ -129,24 +539,882 @@ mod test {
             Test {
                 header: BoxHeader {
                     id: [b't', b'e', b's', b't'],
-                    size: 108 + 108 + 24 + 8
+                    size: 108 + 108 + 24 + 8,
+                    largesize: false,
+                    usertype: None,
                 },
                 ftyp: ftyp_body,
                 data: vec![
                     Data {
                         header: BoxHeader {
                             id: [b'd', b'a', b't', b'a'],
-                            size: 108
+                            size: 108,
+                            largesize: false,
+                            usertype: None,
                         },
                     },
                     Data {
                         header: BoxHeader {
                             id: [b'd', b'a', b't', b'a'],
-                            size: 108
+                            size: 108,
+                            largesize: false,
+                            usertype: None,
                         },
                     }
                 ]
             }
         );
     }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let mut pair = Vec::new();
+        pair.write_u32(8 + 20 + 12 + 16).await.unwrap();
+        pair.write_all(b"pair").await.unwrap();
+        pair.write_u32(20).await.unwrap();
+        pair.write_all(b"ftyprustmp4rfoo0").await.unwrap();
+        pair.write_u32(12).await.unwrap();
+        pair.write_all(b"foo ").await.unwrap();
+        pair.write_u32(0xdeadbeef).await.unwrap();
+        pair.write_u32(16).await.unwrap();
+        pair.write_all(b"bar ").await.unwrap();
+        pair.write_all(&[1, 2, 3, 4, 5, 6, 7, 8]).await.unwrap();
+
+        let mut reader = Reader::new(Cursor::new(pair.clone()), pair.len() as u64);
+        let pair_header = BoxHeader::read(&mut reader).await.unwrap();
+        let pair_body = Pair::read_body(pair_header, &mut reader).await.unwrap();
+        assert_eq!(
+            pair_body.leaves[0],
+            TestLeaf::Foo(
+                BoxHeader {
+                    id: *b"foo ",
+                    size: 12,
+                    largesize: false,
+                    usertype: None,
+                },
+                0xdeadbeef
+            )
+        );
+
+        let mut written = Vec::new();
+        pair_body.write_attr(&mut written).await.unwrap();
+        assert_eq!(pair_body.attr_size(), pair.len() as u64);
+        assert_eq!(written, pair);
+    }
+
+    #[test]
+    fn test_blocking() {
+        let mut pair = Vec::new();
+        pair.extend_from_slice(&(8u32 + 20 + 12).to_be_bytes());
+        pair.extend_from_slice(b"pair");
+        pair.extend_from_slice(&20u32.to_be_bytes());
+        pair.extend_from_slice(b"ftyprustmp4rfoo0");
+        pair.extend_from_slice(&12u32.to_be_bytes());
+        pair.extend_from_slice(b"foo ");
+        pair.extend_from_slice(&0xdeadbeefu32.to_be_bytes());
+
+        let mut reader = blocking::Reader::new(Cursor::new(pair.clone()), pair.len() as u64);
+        let pair_header = BoxHeader::read_blocking(&mut reader).unwrap();
+        let pair_body: Pair = blocking::BoxRead::read_body(pair_header, &mut reader).unwrap();
+        assert_eq!(reader.pos, pair.len() as u64);
+        assert_eq!(
+            pair_body.ftyp.compatible_brands,
+            vec![[b'f', b'o', b'o', b'0']]
+        );
+        assert_eq!(
+            pair_body.leaves,
+            vec![TestLeaf::Foo(
+                BoxHeader {
+                    id: *b"foo ",
+                    size: 12,
+                    largesize: false,
+                    usertype: None,
+                },
+                0xdeadbeef
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_error_path() {
+        let mut pair = Vec::new();
+        pair.write_u32(8 + 20 + 12 + 10).await.unwrap();
+        pair.write_all(b"pair").await.unwrap();
+        pair.write_u32(20).await.unwrap();
+        pair.write_all(b"ftyprustmp4rfoo0").await.unwrap();
+        pair.write_u32(12).await.unwrap();
+        pair.write_all(b"foo ").await.unwrap();
+        pair.write_u32(0xdeadbeef).await.unwrap();
+        pair.write_u32(10).await.unwrap();
+        pair.write_all(b"foo ").await.unwrap();
+        pair.write_u16(0xbeef).await.unwrap();
+
+        let mut reader = Reader::new(Cursor::new(pair.clone()), pair.len() as u64);
+        let err = Pair::read_attr(&mut reader).await.unwrap_err();
+        assert_eq!(err.path.to_string(), "pair/foo [1]");
+        assert_eq!(err.offset, 48);
+        assert!(matches!(
+            err.kind,
+            ErrorKind::Truncated {
+                requested: 4,
+                available: 2
+            }
+        ));
+    }
+
+    #[test]
+    fn test_missing_child() {
+        let mut pair = Vec::new();
+        pair.extend_from_slice(&(8u32 + 12).to_be_bytes());
+        pair.extend_from_slice(b"pair");
+        pair.extend_from_slice(&12u32.to_be_bytes());
+        pair.extend_from_slice(b"foo ");
+        pair.extend_from_slice(&0xdeadbeefu32.to_be_bytes());
+
+        let mut reader = blocking::Reader::from_slice(&pair);
+        reader.path = BoxPath::default().join(*b"moov", None);
+        let pair_header = BoxHeader::read_blocking(&mut reader).unwrap();
+        reader.path = reader.path.join(pair_header.id, None);
+        let err = <Pair as blocking::BoxRead>::read_body(pair_header, &mut reader).unwrap_err();
+        assert_eq!(err.path.to_string(), "moov/pair");
+        assert!(matches!(err.kind, ErrorKind::MissingChild { field } if field == "ftyp"));
+    }
+
+    #[tokio::test]
+    async fn test_stream() {
+        let mut src = Vec::new();
+        src.write_u32(20).await.unwrap();
+        src.write_all(b"ftyprustmp4rfoo0").await.unwrap();
+        src.write_u32(8 + 20 + 12).await.unwrap();
+        src.write_all(b"pair").await.unwrap();
+        src.write_u32(20).await.unwrap();
+        src.write_all(b"ftyprustmp4rfoo0").await.unwrap();
+        src.write_u32(12).await.unwrap();
+        src.write_all(b"foo ").await.unwrap();
+        src.write_u32(0xdeadbeef).await.unwrap();
+
+        let mut stream = stream::Stream::new(&src[..]);
+        let mut pairs = Vec::new();
+        while let Some(header) = stream.next_header().await.unwrap() {
+            if Pair::acceptable_tag(header.id) {
+                pairs.push(stream.read_body::<Pair>(header).await.unwrap());
+            }
+        }
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].ftyp.major_brand, *b"rust");
+    }
+
+    #[tokio::test]
+    async fn test_root_enum() {
+        let mut heif = Vec::new();
+        heif.write_u32(20).await.unwrap();
+        heif.write_all(b"ftypmif1\0\0\0\0heic").await.unwrap();
+        heif.write_u32(8 + 4 + 12).await.unwrap();
+        heif.write_all(b"meta").await.unwrap();
+        heif.write_u32(0).await.unwrap();
+        heif.write_u32(12).await.unwrap();
+        heif.write_all(b"hdlr").await.unwrap();
+        heif.write_all(b"pict").await.unwrap();
+        let mut reader = Reader::new(Cursor::new(heif.clone()), heif.len() as u64);
+        let layout = Layout::read(&mut reader).await.unwrap();
+        assert!(matches!(&layout, Layout::Heif { meta, .. } if &meta.hdlr.handler_type == b"pict"));
+
+        let mut fragmented = Vec::new();
+        for _ in 0..2 {
+            fragmented.write_u32(8).await.unwrap();
+            fragmented.write_all(b"data").await.unwrap();
+        }
+        let layout: Layout =
+            blocking::RootRead::read(&mut blocking::Reader::from_slice(&fragmented)).unwrap();
+        assert!(matches!(&layout, Layout::Fragmented { ftyp: None, data } if data.len() == 2));
+
+        let mut legacy = Vec::new();
+        legacy.write_u32(8 + 20).await.unwrap();
+        legacy.write_all(b"pair").await.unwrap();
+        legacy.write_u32(20).await.unwrap();
+        legacy.write_all(b"ftyprustmp4rfoo0").await.unwrap();
+        let layout: Layout =
+            blocking::RootRead::read(&mut blocking::Reader::from_slice(&legacy)).unwrap();
+        assert!(matches!(layout, Layout::Legacy(pair) if pair.leaves.is_empty()));
+
+        let err = IsoOnly::read(&mut Reader::new(
+            Cursor::new(heif.clone()),
+            heif.len() as u64,
+        ))
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err.kind,
+            ErrorKind::UnknownFileType { brands } if brands.len() == 2 && &brands[1].raw == b"heic"
+        ));
+        let iso: IsoOnly = blocking::RootRead::read(&mut blocking::Reader::from_slice(
+            b"\0\0\0\x10ftypisom\0\0\0\0",
+        ))
+        .unwrap();
+        assert!(matches!(iso, IsoOnly::Iso { ftyp } if ftyp.major_brand == *b"isom"));
+    }
+
+    #[tokio::test]
+    async fn test_lazy() {
+        let mut pair = Vec::new();
+        pair.write_u32(8 + 20 + 12 + 16).await.unwrap();
+        pair.write_all(b"pair").await.unwrap();
+        pair.write_u32(20).await.unwrap();
+        pair.write_all(b"ftyprustmp4rfoo0").await.unwrap();
+        pair.write_u32(12).await.unwrap();
+        pair.write_all(b"foo ").await.unwrap();
+        pair.write_u32(0xdeadbeef).await.unwrap();
+        pair.write_u32(16).await.unwrap();
+        pair.write_all(b"bar ").await.unwrap();
+        pair.write_all(&[1, 2, 3, 4, 5, 6, 7, 8]).await.unwrap();
+
+        let mut reader = Reader::new(Cursor::new(pair.clone()), pair.len() as u64);
+        let lazy = LazyPair::read_attr(&mut reader).await.unwrap();
+        assert_eq!(reader.pos, lazy.header.size);
+        assert!(lazy.ftyp.get().is_none());
+        assert_eq!(lazy.ftyp.offset(), 16);
+        assert_eq!(lazy.leaves[1].header().id, *b"bar ");
+
+        let ftyp = lazy.ftyp.load(&reader).await.unwrap();
+        assert_eq!(ftyp.compatible_brands, vec![*b"foo0"]);
+        assert!(lazy.ftyp.get().is_some());
+        assert_eq!(
+            *lazy.leaves[1].load(&reader).await.unwrap(),
+            TestLeaf::Bar {
+                header: BoxHeader {
+                    id: *b"bar ",
+                    size: 16,
+                    largesize: false,
+                    usertype: None,
+                },
+                foo: [1, 2, 3, 4, 5, 6, 7, 8],
+            }
+        );
+
+        let reader = blocking::Reader::from_slice(&pair);
+        let lazy: LazyPair = blocking::AttrRead::read_attr(&mut reader.clone()).unwrap();
+        assert_eq!(
+            lazy.leaves[0].load_blocking(&reader).unwrap(),
+            &TestLeaf::Foo(
+                BoxHeader {
+                    id: *b"foo ",
+                    size: 12,
+                    largesize: false,
+                    usertype: None,
+                },
+                0xdeadbeef
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_uuid() {
+        const TFXD: [u8; 16] = [
+            0x6d, 0x1d, 0x9b, 0x05, 0x42, 0xd5, 0x44, 0xe6, 0x80, 0xe2, 0x14, 0x1d, 0xaf, 0xf7,
+            0x57, 0xb2,
+        ];
+        const TFRF: [u8; 16] = [
+            0xd4, 0x80, 0x7e, 0xf2, 0xca, 0x39, 0x46, 0x95, 0x8e, 0x54, 0x26, 0xcb, 0x9e, 0x46,
+            0xa7, 0x9f,
+        ];
+        let mut traf = Vec::new();
+        traf.write_u32(8 + 25 + 28).await.unwrap();
+        traf.write_all(b"traf").await.unwrap();
+        traf.write_u32(25).await.unwrap();
+        traf.write_all(b"uuid").await.unwrap();
+        traf.write_all(&TFRF).await.unwrap();
+        traf.write_u8(3).await.unwrap();
+        traf.write_u32(28).await.unwrap();
+        traf.write_all(b"uuid").await.unwrap();
+        traf.write_all(&TFXD).await.unwrap();
+        traf.write_u32(1000).await.unwrap();
+
+        let mut reader = Reader::new(Cursor::new(traf.clone()), traf.len() as u64);
+        let parsed = Traf::read_attr(&mut reader).await.unwrap();
+        assert_eq!(parsed.tfxd.header.usertype, Some(TFXD));
+        assert_eq!(parsed.tfxd.header.header_size(), 24);
+        assert_eq!(parsed.tfxd.time, 1000);
+        assert_eq!(parsed.tfrf.as_ref().map(|tfrf| tfrf.count), Some(3));
+        assert!(!Tfxd::acceptable_header(
+            &parsed.tfrf.as_ref().unwrap().header
+        ));
+
+        let mut written = Vec::new();
+        parsed.write_attr(&mut written).await.unwrap();
+        // fields are written in declaration order
+        assert_eq!(&written[8..8 + 28], &traf[8 + 25..]);
+        assert_eq!(&written[8 + 28..], &traf[8..8 + 25]);
+    }
+
+    #[tokio::test]
+    async fn test_fullbox() {
+        let mut mdhd = Vec::new();
+        mdhd.write_u32(16).await.unwrap();
+        mdhd.write_all(b"mdhd").await.unwrap();
+        mdhd.write_all(&[0, 0, 0, 1]).await.unwrap();
+        mdhd.write_u32(600).await.unwrap();
+
+        let parsed: Mdhd =
+            blocking::AttrRead::read_attr(&mut blocking::Reader::from_slice(&mdhd)).unwrap();
+        assert_eq!(parsed.fullbox.version, 0);
+        assert!(parsed.fullbox.flags.contains(1));
+        assert_eq!(parsed.timescale, 600);
+        let mut written = Vec::new();
+        parsed.write_attr(&mut written).await.unwrap();
+        assert_eq!(written, mdhd);
+
+        mdhd[8] = 1;
+        let err = Mdhd::read_attr(&mut Reader::new(Cursor::new(mdhd.clone()), 16))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.kind,
+            ErrorKind::UnsupportedVersion { version: 1 }
+        ));
+        assert_eq!((err.path.to_string().as_str(), err.offset), ("mdhd", 8));
+    }
+
+    #[tokio::test]
+    async fn test_cond() {
+        let mut v1 = Vec::new();
+        v1.write_u32(8 + 4 + 8 + 2 + 4).await.unwrap();
+        v1.write_all(b"tfdt").await.unwrap();
+        v1.write_all(&[1, 0, 0, 1]).await.unwrap();
+        v1.write_u64(1 << 40).await.unwrap();
+        v1.write_u16(2).await.unwrap();
+        v1.write_u32(0x0200_0000).await.unwrap();
+        let tfdt = Tfdt::read_attr(&mut Reader::new(Cursor::new(v1.clone()), v1.len() as u64))
+            .await
+            .unwrap();
+        assert_eq!(tfdt.base_media_decode_time, Either::Left(1 << 40));
+        assert_eq!(tfdt.sample_count, Some(2));
+        assert_eq!(tfdt.first_sample_flags, Some(0x0200_0000));
+        let mut written = Vec::new();
+        tfdt.write_attr(&mut written).await.unwrap();
+        assert_eq!(written, v1);
+
+        let mut v0 = Vec::new();
+        v0.write_u32(8 + 4 + 4).await.unwrap();
+        v0.write_all(b"tfdt").await.unwrap();
+        v0.write_all(&[0, 0, 0, 0]).await.unwrap();
+        v0.write_u32(1000).await.unwrap();
+        let tfdt: Tfdt =
+            blocking::AttrRead::read_attr(&mut blocking::Reader::from_slice(&v0)).unwrap();
+        assert_eq!(tfdt.base_media_decode_time.to_u64(), 1000);
+        assert_eq!((tfdt.sample_count, tfdt.first_sample_flags), (None, None));
+    }
+
+    #[tokio::test]
+    async fn test_count() {
+        let mut stco = Vec::new();
+        stco.write_u32(8 + 4 + 12).await.unwrap();
+        stco.write_all(b"stco").await.unwrap();
+        stco.write_u32(2).await.unwrap();
+        stco.write_u32(100).await.unwrap();
+        stco.write_u32(200).await.unwrap();
+        stco.write_u32(0xffff_ffff).await.unwrap();
+        // bytes after the declared entries would be lost
+        let err = Stco::read_attr(&mut Reader::new(Cursor::new(stco.clone()), 24))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.kind,
+            ErrorKind::TrailingBytes { field, count: 2, remaining: 4 } if field == "chunk_offsets"
+        ));
+
+        stco[11] = 3;
+        let parsed = Stco::read_attr(&mut Reader::new(Cursor::new(stco.clone()), 24))
+            .await
+            .unwrap();
+        assert_eq!(parsed.chunk_offsets, vec![100, 200, 0xffff_ffff]);
+
+        stco[11] = 4;
+        let err = <Stco as blocking::AttrRead>::read_attr(&mut blocking::Reader::from_slice(&stco))
+            .unwrap_err();
+        assert!(matches!(
+            err.kind,
+            ErrorKind::CountMismatch { field, count: 4, read: 3 } if field == "chunk_offsets"
+        ));
+        assert_eq!((err.path.to_string().as_str(), err.offset), ("stco", 24));
+    }
+
+    #[tokio::test]
+    async fn test_checks() {
+        let mut rang = Vec::new();
+        rang.write_u32(8 + 4 + 4 + 2 + 8).await.unwrap();
+        rang.write_all(b"rang").await.unwrap();
+        rang.write_all(b"rng1").await.unwrap();
+        rang.write_u32(600).await.unwrap();
+        rang.write_u16(0).await.unwrap();
+        rang.write_u32(1).await.unwrap();
+        rang.write_u32(2).await.unwrap();
+        let parsed = Range::read_attr(&mut Reader::new(Cursor::new(rang.clone()), 26))
+            .await
+            .unwrap();
+        assert_eq!((parsed.timescale, parsed.first, parsed.last), (600, 1, 2));
+
+        let read = |src: &[u8]| {
+            <Range as blocking::AttrRead>::read_attr(&mut blocking::Reader::from_slice(src))
+                .unwrap_err()
+        };
+        let mut bad_magic = rang.clone();
+        bad_magic[11] = b'2';
+        let err = read(&bad_magic);
+        assert!(matches!(
+            &err.kind,
+            ErrorKind::MagicMismatch { field, expected, found }
+                if field == "magic" && expected == "[114, 110, 103, 49]" && found == "[114, 110, 103, 50]"
+        ));
+        assert_eq!((err.path.to_string().as_str(), err.offset), ("rang", 8));
+
+        let mut zero_timescale = rang.clone();
+        zero_timescale[12..16].fill(0);
+        let err = read(&zero_timescale);
+        assert!(matches!(
+            &err.kind,
+            ErrorKind::AssertionFailed { field, assertion }
+                if field == "timescale" && assertion == "*timescale != 0"
+        ));
+        assert_eq!(err.offset, 12);
+
+        let mut reserved = rang.clone();
+        reserved[17] = 1;
+        let err = read(&reserved);
+        assert!(matches!(&err.kind, ErrorKind::MagicMismatch { field, .. } if field == "reserved"));
+        assert_eq!(err.offset, 16);
+
+        // the whole box is checked after its fields
+        let mut reversed = rang.clone();
+        reversed[21] = 3;
+        let err = read(&reversed);
+        assert!(matches!(&err.kind, ErrorKind::Invalid { reason } if reason == "3 > 2"));
+        assert_eq!((err.path.to_string().as_str(), err.offset), ("rang", 8));
+    }
+
+    #[tokio::test]
+    async fn test_custom_fields() {
+        let mut name = Vec::new();
+        name.write_u32(8 + 4 + 3 + 1 + 2).await.unwrap();
+        name.write_all(b"name").await.unwrap();
+        name.write_u8(3).await.unwrap();
+        name.write_all(b"abc").await.unwrap();
+        name.write_all(&[0xff, 0xff, 0xff, 7]).await.unwrap();
+        name.write_u16(640).await.unwrap();
+        let parsed = Name::read_attr(&mut Reader::new(Cursor::new(name.clone()), 18))
+            .await
+            .unwrap();
+        let blocking: Name =
+            blocking::AttrRead::read_attr(&mut blocking::Reader::from_slice(&name)).unwrap();
+        assert_eq!(parsed, blocking);
+        assert_eq!(parsed.name, "abc");
+        // reserved bytes are not kept and computed fields are not in the file
+        assert_eq!((parsed.flags, parsed.width), (0, 640));
+        assert_eq!(parsed.double_width, 1280);
+        assert!(parsed.cache.is_empty());
+
+        let mut written = Vec::new();
+        parsed.write_attr(&mut written).await.unwrap();
+        assert_eq!(&written[..12], &name[..12]);
+        assert_eq!(&written[12..], [0, 0, 0, 0, 2, 128]);
+    }
+
+    #[tokio::test]
+    async fn test_trailing_options() {
+        let mut btrt = Vec::new();
+        btrt.write_u32(8 + 12).await.unwrap();
+        btrt.write_all(b"btrt").await.unwrap();
+        for value in [1u32, 2, 3] {
+            btrt.write_u32(value).await.unwrap();
+        }
+        let parsed = Btrt::read_attr(&mut Reader::new(Cursor::new(btrt.clone()), 20))
+            .await
+            .unwrap();
+        assert_eq!((parsed.max_bitrate, parsed.avg_bitrate), (Some(2), Some(3)));
+        let mut written = Vec::new();
+        parsed.write_attr(&mut written).await.unwrap();
+        assert_eq!(written, btrt);
+
+        let read = |src: &[u8]| -> Btrt {
+            blocking::AttrRead::read_attr(&mut blocking::Reader::from_slice(src)).unwrap()
+        };
+        // written by an older muxer without the bitrates
+        let mut short = btrt[..12].to_vec();
+        short[3] = 12;
+        let parsed = read(&short);
+        assert_eq!(
+            (parsed.buffer_size, parsed.max_bitrate, parsed.avg_bitrate),
+            (1, None, None)
+        );
+        // bytes too few for a field are left unread
+        let mut partial = btrt[..18].to_vec();
+        partial[3] = 18;
+        let parsed = read(&partial);
+        assert_eq!((parsed.max_bitrate, parsed.avg_bitrate), (Some(2), None));
+
+        // required fields still fail
+        let mut truncated = btrt[..10].to_vec();
+        truncated[3] = 10;
+        let err =
+            <Btrt as blocking::AttrRead>::read_attr(&mut blocking::Reader::from_slice(&truncated))
+                .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Truncated { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_bits() {
+        let mut avcc = vec![0, 0, 0, 14];
+        avcc.extend_from_slice(b"avcC");
+        avcc.extend_from_slice(&[1, 0b1111_1101, 0b1110_0010, 0b1000_0000, 0x05, 9]);
+        let parsed = AvcC::read_attr(&mut Reader::new(Cursor::new(avcc.clone()), 14))
+            .await
+            .unwrap();
+        assert_eq!(
+            (
+                parsed.length_size_minus_one,
+                parsed.sps_reserved,
+                parsed.sps_count
+            ),
+            (1, 0b111, 2)
+        );
+        assert_eq!((parsed.flag, parsed.wide, parsed.tail), (true, 5, 9));
+        let mut written = Vec::new();
+        parsed.write_attr(&mut written).await.unwrap();
+        assert_eq!(written, avcc);
+
+        let mut wrong = avcc.clone();
+        wrong[9] = 0b0000_0001;
+        let err =
+            <AvcC as blocking::AttrRead>::read_attr(&mut blocking::Reader::from_slice(&wrong))
+                .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::MagicMismatch { .. }));
+        // the run of bit fields starts at 9
+        assert_eq!(err.offset, 9);
+    }
+
+    #[tokio::test]
+    async fn test_hybrid() {
+        let mut meta = Vec::new();
+        meta.write_u32(8 + 4 + 8 + 12).await.unwrap();
+        meta.write_all(b"meta").await.unwrap();
+        meta.write_u32(0).await.unwrap();
+        meta.write_u32(8).await.unwrap();
+        meta.write_all(b"data").await.unwrap();
+        meta.write_u32(12).await.unwrap();
+        meta.write_all(b"hdlr").await.unwrap();
+        meta.write_all(b"mdir").await.unwrap();
+        let parsed = Meta::read_attr(&mut Reader::new(Cursor::new(meta.clone()), 32))
+            .await
+            .unwrap();
+        assert_eq!(parsed.fullbox.version, 0);
+        assert_eq!(&parsed.hdlr.handler_type, b"mdir");
+        assert_eq!(parsed.data.len(), 1);
+
+        // without an #[mp4(order)] field, children are written in field order
+        let mut written = Vec::new();
+        parsed.write_attr(&mut written).await.unwrap();
+        assert_eq!(&written[..12], &meta[..12]);
+        assert_eq!(&written[12..24], &meta[20..32]);
+        assert_eq!(&written[24..], &meta[12..20]);
+
+        let mut avc1 = Vec::new();
+        avc1.write_u32(8 + 2 + 12 + 16 + 4).await.unwrap();
+        avc1.write_all(b"avc1").await.unwrap();
+        avc1.write_u16(1).await.unwrap();
+        avc1.write_u32(12).await.unwrap();
+        avc1.write_all(b"btrt").await.unwrap();
+        avc1.write_u32(1024).await.unwrap();
+        avc1.write_u32(16).await.unwrap();
+        avc1.write_all(b"pasp").await.unwrap();
+        avc1.write_u32(4).await.unwrap();
+        avc1.write_u32(3).await.unwrap();
+        // zero terminator
+        avc1.write_u32(0).await.unwrap();
+        let mut reader = blocking::Reader::from_slice(&avc1);
+        let entry: SampleEntry = blocking::AttrRead::read_attr(&mut reader).unwrap();
+        assert_eq!(reader.pos, avc1.len() as u64);
+        let SampleEntry::Avc1 {
+            data_reference_index,
+            pasp: Some(pasp),
+            btrt: Some(btrt),
+            order,
+            trailer,
+            ..
+        } = &entry
+        else {
+            panic!("unexpected {:?}", entry);
+        };
+        assert_eq!(*data_reference_index, 1);
+        assert_eq!((pasp.h_spacing, pasp.v_spacing), (4, 3));
+        assert_eq!(btrt.buffer_size, 1024);
+        assert_eq!(order, &[1, 0]);
+        assert_eq!(trailer.as_ref(), [0, 0, 0, 0]);
+
+        // btrt is declared after pasp but written back before it, as read
+        let mut written = Vec::new();
+        entry.write_attr(&mut written).await.unwrap();
+        assert_eq!(written, avc1);
+    }
+
+    #[tokio::test]
+    async fn test_leaf_trailer() {
+        let mut smhd = Vec::new();
+        smhd.write_u32(8 + 2 + 3).await.unwrap();
+        smhd.write_all(b"smhd").await.unwrap();
+        smhd.write_u16(0x0100).await.unwrap();
+        smhd.write_all(&[1, 2, 3]).await.unwrap();
+        let parsed = Smhd::read_attr(&mut Reader::new(Cursor::new(smhd.clone()), 13))
+            .await
+            .unwrap();
+        assert_eq!(parsed.balance, 0x0100);
+        assert_eq!(parsed.trailer.as_ref(), [1, 2, 3]);
+        let blocking: Smhd =
+            blocking::AttrRead::read_attr(&mut blocking::Reader::from_slice(&smhd)).unwrap();
+        assert_eq!(blocking, parsed);
+
+        let mut written = Vec::new();
+        parsed.write_attr(&mut written).await.unwrap();
+        assert_eq!(written, smhd);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_children() {
+        let mut udta = Vec::new();
+        udta.write_u32(8 + 10 + 12 + 9 + 4).await.unwrap();
+        udta.write_all(b"udta").await.unwrap();
+        udta.write_u32(10).await.unwrap();
+        udta.write_all(b"\xa9nam").await.unwrap();
+        udta.write_all(b"ab").await.unwrap();
+        udta.write_u32(12).await.unwrap();
+        udta.write_all(b"hdlr").await.unwrap();
+        udta.write_all(b"mdir").await.unwrap();
+        udta.write_u32(9).await.unwrap();
+        udta.write_all(b"\xa9day").await.unwrap();
+        udta.write_u8(7).await.unwrap();
+        // zero terminator
+        udta.write_u32(0).await.unwrap();
+        let parsed = Udta::read_attr(&mut Reader::new(Cursor::new(udta.clone()), 43))
+            .await
+            .unwrap();
+        assert_eq!(&parsed.hdlr.as_ref().unwrap().handler_type, b"mdir");
+        assert_eq!(
+            parsed
+                .others
+                .iter()
+                .map(|raw| (raw.header.id, raw.offset, raw.data.as_ref()))
+                .collect::<Vec<_>>(),
+            vec![
+                (*b"\xa9nam", 8, b"ab".as_ref()),
+                (*b"\xa9day", 30, [7].as_ref())
+            ]
+        );
+        let blocking: Udta =
+            blocking::AttrRead::read_attr(&mut blocking::Reader::from_slice(&udta)).unwrap();
+        assert_eq!(blocking, parsed);
+
+        assert_eq!(parsed.order, [0, 1, 0]);
+        assert_eq!(parsed.trailer.as_ref(), [0, 0, 0, 0]);
+
+        // unknown children stay between the known ones and the terminator is kept
+        let mut written = Vec::new();
+        parsed.write_attr(&mut written).await.unwrap();
+        assert_eq!(written, udta);
+    }
+
+    #[tokio::test]
+    async fn test_internal_enum() {
+        let mut src = Vec::new();
+        src.write_u32(8 + 12).await.unwrap();
+        src.write_all(b"meta").await.unwrap();
+        src.write_u32(12).await.unwrap();
+        src.write_all(b"hdlr").await.unwrap();
+        src.write_all(b"mdir").await.unwrap();
+        src.write_u32(8 + 16).await.unwrap();
+        src.write_all(b"dinf").await.unwrap();
+        for _ in 0..2 {
+            src.write_u32(8).await.unwrap();
+            src.write_all(b"data").await.unwrap();
+        }
+        src.write_u32(10).await.unwrap();
+        src.write_all(b"free").await.unwrap();
+        src.write_all(b"ab").await.unwrap();
+        let containers = Vec::<Container>::read_attr(&mut Reader::new(
+            Cursor::new(src.clone()),
+            src.len() as u64,
+        ))
+        .await
+        .unwrap();
+        assert!(
+            matches!(&containers[0], Container::Meta { hdlr, .. } if &hdlr.handler_type == b"mdir")
+        );
+        assert!(matches!(&containers[1], Container::Dinf(_, data) if data.len() == 2));
+        assert_eq!(<Container as BoxRead>::TAGS, None);
+        assert_eq!(
+            <Vec<Data> as movparse_box::ChildTags>::TAGS,
+            Some(&[*b"data"][..])
+        );
+        assert!(
+            matches!(&containers[2], Container::Unknown(raw) if &raw.header.id == b"free" && raw.offset == 44)
+        );
+        let mut written = Vec::new();
+        containers.write_attr(&mut written).await.unwrap();
+        assert_eq!(written, src);
+
+        // without a fallback variant, read_body rejects other boxes instead of panicking
+        let header = BoxHeader {
+            id: *b"baz ",
+            size: 12,
+            largesize: false,
+            usertype: None,
+        };
+        let err = <TestLeaf as blocking::BoxRead>::read_body(
+            header,
+            &mut blocking::Reader::from_slice(&[0; 4]),
+        )
+        .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::UnexpectedTag { tag } if &tag.raw == b"baz "));
+    }
+
+    #[tokio::test]
+    async fn test_numeric_tags() {
+        assert_eq!(
+            <Metadata as BoxRead>::TAGS,
+            Some(&[*b"\xA9nam", *b"\xA9day", *b"\xA9ART"][..])
+        );
+        let mut src = Vec::new();
+        for (tag, value) in [(b"\xA9ART", 1u8), (b"\xA9nam", 2), (b"\xA9day", 3)] {
+            src.write_u32(9).await.unwrap();
+            src.write_all(tag).await.unwrap();
+            src.write_u8(value).await.unwrap();
+        }
+        let parsed: Vec<Metadata> =
+            blocking::AttrRead::read_attr(&mut blocking::Reader::from_slice(&src)).unwrap();
+        assert!(matches!(
+            parsed[..],
+            [
+                Metadata::Artist(_, 1),
+                Metadata::Name(_, 2),
+                Metadata::Day(_, 3)
+            ]
+        ));
+        let mut written = Vec::new();
+        parsed.write_attr(&mut written).await.unwrap();
+        assert_eq!(written, src);
+    }
+
+    #[tokio::test]
+    async fn test_generics() {
+        let mut trak = Vec::new();
+        trak.write_u32(8 + 12 + 12).await.unwrap();
+        trak.write_all(b"trak").await.unwrap();
+        trak.write_u32(12).await.unwrap();
+        trak.write_all(b"valu").await.unwrap();
+        trak.write_u32(600).await.unwrap();
+        trak.write_u32(12).await.unwrap();
+        trak.write_all(b"hdlr").await.unwrap();
+        trak.write_all(b"vide").await.unwrap();
+        let parsed = GenericTrak::<Value<u32>>::read_attr(&mut Reader::new(
+            Cursor::new(trak.clone()),
+            trak.len() as u64,
+        ))
+        .await
+        .unwrap();
+        assert_eq!(parsed.extra.as_ref().map(|extra| extra.value), Some(600));
+        let blocking: GenericTrak<Value<u32>> =
+            blocking::AttrRead::read_attr(&mut blocking::Reader::from_slice(&trak)).unwrap();
+        assert_eq!(blocking, parsed);
+
+        // the same track carries another payload type
+        let parsed: GenericTrak<Value<[u8; 4]>> =
+            blocking::AttrRead::read_attr(&mut blocking::Reader::from_slice(&trak)).unwrap();
+        assert_eq!(parsed.extra.unwrap().value, 600u32.to_be_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_attr_derive() {
+        let src = [2u8, 0, 0, 0, 3, 0, 10, 1, 0, 0, 0, 1, 0, 20];
+        let mut reader = Reader::new(Cursor::new(src), src.len() as u64);
+        let entries = Vec::<KindEntry>::read_attr(&mut reader).await.unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                KindEntry(
+                    Kind::Audio,
+                    Entry {
+                        count: 3,
+                        delta: 10
+                    }
+                ),
+                KindEntry(
+                    Kind::Video,
+                    Entry {
+                        count: 1,
+                        delta: 20
+                    }
+                ),
+            ]
+        );
+        assert_eq!(entries.attr_size(), 14);
+        let mut written = Vec::new();
+        entries.write_attr(&mut written).await.unwrap();
+        assert_eq!(written, src);
+
+        let err = <Kind as blocking::AttrRead>::read_attr(&mut blocking::Reader::from_slice(&[7]))
+            .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::UnknownValue { value: 7 }));
+    }
+
+    #[tokio::test]
+    async fn test_borrow() {
+        let free = |kind: u8, data: &[u8]| {
+            let mut free = vec![
+                0,
+                0,
+                0,
+                12 + data.len() as u8,
+                b'f',
+                b'r',
+                b'e',
+                b'e',
+                0,
+                0,
+                0,
+                kind,
+            ];
+            free.extend_from_slice(data);
+            free
+        };
+        let mut src = vec![0, 0, 0, 0, b'p', b'a', b'i', b'r'];
+        src.extend_from_slice(&[
+            0, 0, 0, 16, b'f', b't', b'y', b'p', b'i', b's', b'o', b'm', 0, 0, 0, 1,
+        ]);
+        src.extend(free(1, &[1, 2, 3]));
+        src.extend_from_slice(&[0, 0, 0, 9, b's', b'k', b'i', b'p', 7]);
+        src.extend(free(2, &[]));
+        src[3] = src.len() as u8;
+
+        let file =
+            <FileRef as slice::RootRead>::read(&mut slice::Reader::from_slice(&src)).unwrap();
+        let pair = &file.pair;
+        assert_eq!(pair.ftyp.major_brand, *b"isom");
+        assert_eq!(pair.frees.len(), 2);
+        assert_eq!(
+            (pair.frees[0].kind, pair.frees[0].data),
+            (1, &[1, 2, 3][..])
+        );
+        assert_eq!(pair.frees[0].data.as_ptr(), src[36..].as_ptr());
+        assert_eq!((pair.frees[1].kind, pair.frees[1].data), (2, &[][..]));
+        assert_eq!(pair.others.len(), 1);
+        assert_eq!(pair.order, [0, 1, 2, 1]);
+
+        let read: PairRef =
+            slice::AttrRead::read_attr(&mut slice::Reader::from_slice(&src)).unwrap();
+        assert_eq!(&read, pair);
+        let mut written = Vec::new();
+        pair.write_attr(&mut written).await.unwrap();
+        assert_eq!(written, src);
+
+        let err = <FreeRef as slice::AttrRead>::read_attr(&mut slice::Reader::from_slice(&src))
+            .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::UnexpectedTag { .. }));
+    }
 }
@@ -0,0 +1,14 @@
+use movparse_box::BoxHeader;
+use movparse_derive::BoxRead;
+
+#[derive(BoxRead)]
+#[mp4(boxtype = "leaf")]
+enum Sample {
+    #[mp4(tag = "avc1")]
+    Avc(#[mp4(header)] BoxHeader),
+    #[mp4(tag = "hev1")]
+    #[mp4(tag = "avc1")]
+    Hevc(#[mp4(header)] BoxHeader),
+}
+
+fn main() {}
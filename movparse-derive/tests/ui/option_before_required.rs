@@ -0,0 +1,14 @@
+use movparse_box::BoxHeader;
+use movparse_derive::BoxRead;
+
+#[derive(BoxRead)]
+#[mp4(boxtype = "leaf")]
+#[mp4(tag = "btrt")]
+struct Btrt {
+    #[mp4(header)]
+    header: BoxHeader,
+    buffer_size: Option<u32>,
+    max_bitrate: u32,
+}
+
+fn main() {}
@@ -0,0 +1,22 @@
+use movparse_box::BoxHeader;
+use movparse_derive::BoxRead;
+
+#[derive(BoxRead)]
+#[mp4(boxtype = "leaf")]
+#[mp4(tag = "stco")]
+struct Stco {
+    #[mp4(header)]
+    header: BoxHeader,
+}
+
+#[derive(BoxRead)]
+#[mp4(boxtype = "internal")]
+#[mp4(tag = "stbl")]
+struct Stbl {
+    #[mp4(header)]
+    header: BoxHeader,
+    a: Option<Stco>,
+    b: Option<Stco>,
+}
+
+fn main() {}
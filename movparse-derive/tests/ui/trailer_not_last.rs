@@ -0,0 +1,15 @@
+use movparse_box::{BoxHeader, Bytes};
+use movparse_derive::BoxRead;
+
+#[derive(BoxRead)]
+#[mp4(boxtype = "leaf")]
+#[mp4(tag = "smhd")]
+struct Smhd {
+    #[mp4(header)]
+    header: BoxHeader,
+    #[mp4(trailer)]
+    trailer: Bytes,
+    balance: u16,
+}
+
+fn main() {}
@@ -1,13 +1,19 @@
 use std::path::PathBuf;
 
 use clap::Parser;
-use movparse::{Reader, RootRead};
+use movparse::{
+    tree::{BoxTree, CONTAINERS},
+    Reader, RootRead,
+};
 use tokio::fs;
 use tracing_subscriber::{prelude::__tracing_subscriber_SubscriberExt, util::SubscriberInitExt};
 
 #[derive(Parser)]
 struct Opts {
     file: PathBuf,
+    /// Print the box tree instead of parsing it as QuickTime
+    #[clap(long)]
+    tree: bool,
 }
 
 #[tokio::main]
@@ -20,10 +26,13 @@ async fn main() -> anyhow::Result<()> {
     let file = fs::File::open(opts.file).await?;
     let limit = file.metadata().await?.len();
     let mut reader = Reader::new(file, limit);
+    if opts.tree {
+        print!("{}", BoxTree::read(&mut reader, CONTAINERS).await?);
+        return Ok(());
+    }
     let mp4 = movparse::quicktime::QuickTime::read(&mut reader).await?;
     for (idx, sample) in mp4.moov.traks[0].samples()?.iter().enumerate() {
-        let mut buf = Vec::new();
-        buf.resize(sample.size, 0);
+        let mut buf = vec![0; sample.size];
         reader.seek_from_start(sample.offset as u64).await?;
         reader.read_exact(&mut buf).await?;
         println!("buf: {}: {} from: {}", idx, buf.len(), sample.offset);
@@ -1,12 +1,15 @@
 //! Parser implementation for Apple QuickTime format based on [apple document](https://developer.apple.com/library/archive/documentation/QuickTime/QTFF/QTFFChap2/qtff2.html)
-use std::{io, time::Duration};
+use std::time::Duration;
 
-use movparse_box::{AttrRead, BoxHeader, BoxRead, RawString, Reader, U32Tag};
-use movparse_derive::{BoxRead, RootRead};
+use movparse_box::{
+    blocking, BoxHeader, BoxRead, Bytes, Either, Error, ErrorKind, FullBoxHeader, RawBox,
+    RawString, Reader, U32Tag,
+};
+use movparse_derive::{AttrRead, AttrWrite, BoxRead, BoxWrite, RootRead};
 use serde::{Deserialize, Serialize};
 use tokio::io::{AsyncRead, AsyncSeek};
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "leaf")]
 #[mp4(tag = "ftyp")]
 pub struct Ftyp {
@@ -29,11 +32,33 @@ impl Mdat {
         reader: &mut Reader<R>,
         offset: u64,
         buf: &mut [u8],
-    ) -> io::Result<()> {
+    ) -> Result<(), Error> {
         reader.seek_from_start(self.pos + offset).await?;
         reader.read_exact(buf).await?;
         Ok(())
     }
+
+    pub fn read_exact_blocking<R: blocking::Source>(
+        &self,
+        reader: &mut blocking::Reader<R>,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> Result<(), Error> {
+        reader.seek_from_start(self.pos + offset)?;
+        reader.read_exact(buf)?;
+        Ok(())
+    }
+
+    /// Reads `len` bytes from `offset` in the body. Shares the buffer instead of copying when `reader` reads from [`Bytes`].
+    pub fn read_bytes_blocking<R: blocking::Source>(
+        &self,
+        reader: &mut blocking::Reader<R>,
+        offset: u64,
+        len: usize,
+    ) -> Result<Bytes, Error> {
+        reader.seek_from_start(self.pos + offset)?;
+        reader.read_bytes(len)
+    }
 }
 
 #[async_trait::async_trait]
@@ -45,13 +70,28 @@ impl BoxRead for Mdat {
     async fn read_body<R: AsyncRead + AsyncSeek + Unpin + Send>(
         header: BoxHeader,
         reader: &mut Reader<R>,
-    ) -> Result<Self, io::Error> {
+    ) -> Result<Self, Error> {
         let pos = reader.pos;
         reader.seek_from_current(header.body_size() as i64).await?;
         Ok(Self { header, pos })
     }
 }
 
+impl blocking::BoxRead for Mdat {
+    fn acceptable_tag(tag: [u8; 4]) -> bool {
+        tag == [b'm', b'd', b'a', b't']
+    }
+
+    fn read_body<R: blocking::Source>(
+        header: BoxHeader,
+        reader: &mut blocking::Reader<R>,
+    ) -> Result<Self, Error> {
+        let pos = reader.pos;
+        reader.seek_from_current(header.body_size() as i64)?;
+        Ok(Self { header, pos })
+    }
+}
+
 #[derive(Clone, RootRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct QuickTime {
     pub ftyp: Ftyp,
@@ -59,41 +99,35 @@ pub struct QuickTime {
     pub mdat: Mdat,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, AttrRead, AttrWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Timescale(u32);
 
-#[async_trait::async_trait]
-impl AttrRead for Timescale {
-    async fn read_attr<R: AsyncRead + AsyncSeek + Unpin + Send>(
-        reader: &mut Reader<R>,
-    ) -> io::Result<Self> {
-        Ok(Self(u32::read_attr(reader).await?))
-    }
-}
-
 impl Timescale {
-    pub fn decode_duration(&self, dur: u32) -> Duration {
-        Duration::from_secs(dur as u64) / self.0
+    pub fn decode_duration(&self, dur: u64) -> Duration {
+        Duration::from_secs(dur) / self.0
     }
 }
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "leaf")]
 #[mp4(tag = "mvhd")]
+#[mp4(versions = "0,1")]
 pub struct Mvhd {
     #[mp4(header)]
     pub header: BoxHeader,
-    pub version: u8,
-    #[serde(with = "movparse_box::util::serde::u8_array")]
-    pub flags: [u8; 3],
-    pub creation_time: u32,
-    pub modification_time: u32,
+    #[mp4(fullbox)]
+    pub fullbox: FullBoxHeader,
+    #[mp4(if_version = 1)]
+    pub creation_time: Either<u64, u32>,
+    #[mp4(if_version = 1)]
+    pub modification_time: Either<u64, u32>,
+    #[mp4(assert = "time_scale.0 != 0")]
     pub time_scale: Timescale,
-    pub duration: u32,
+    #[mp4(if_version = 1)]
+    pub duration: Either<u64, u32>,
     pub preferred_rate: u32,
     pub preferred_volume: u16,
-    #[serde(with = "movparse_box::util::serde::u8_array")]
-    pub _reserved: [u8; 10],
+    #[mp4(skip = 10)]
     #[serde(with = "movparse_box::util::serde::u8_array")]
     pub matrix_structure: [u8; 36],
     pub preview_time: u32,
@@ -103,93 +137,112 @@ pub struct Mvhd {
     pub selection_duration: u32,
     pub current_time: u32,
     pub next_track_id: u32,
+    #[mp4(trailer)]
+    pub trailer: Bytes,
 }
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "leaf")]
 #[mp4(tag = "tkhd")]
+#[mp4(versions = "0,1")]
 pub struct Tkhd {
     #[mp4(header)]
     pub header: BoxHeader,
-    pub version: u8,
-    pub flags: [u8; 3],
-    pub creation_time: u32,
-    pub modification_time: u32,
+    #[mp4(fullbox)]
+    pub fullbox: FullBoxHeader,
+    #[mp4(if_version = 1)]
+    pub creation_time: Either<u64, u32>,
+    #[mp4(if_version = 1)]
+    pub modification_time: Either<u64, u32>,
     pub trak_id: u32,
+    #[mp4(magic = b"\0\0\0\0")]
     _reserved: [u8; 4],
-    pub duration: u32,
+    #[mp4(if_version = 1)]
+    pub duration: Either<u64, u32>,
+    #[mp4(magic = "[0u8; 8]")]
     _reserved2: [u8; 8],
     pub layer: u16,
     pub alternate_group: u16,
     pub volume: u16,
+    #[mp4(magic = b"\0\0")]
     _reserved3: [u8; 2],
     #[serde(with = "movparse_box::util::serde::u8_array")]
     pub matrix_structure: [u8; 36],
     pub track_width: u32,
     pub track_height: u32,
+    #[mp4(trailer)]
+    pub trailer: Bytes,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, AttrRead, AttrWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Edit {
     pub track_duration: u32,
     pub media_time: u32,
     pub media_rate: u32,
 }
 
-#[async_trait::async_trait]
-impl AttrRead for Edit {
-    async fn read_attr<R: AsyncRead + AsyncSeek + Unpin + Send>(
-        reader: &mut Reader<R>,
-    ) -> Result<Self, io::Error> {
-        let track_duration = u32::read_attr(reader).await?;
-        let media_time = u32::read_attr(reader).await?;
-        let media_rate = u32::read_attr(reader).await?;
-        Ok(Self {
-            track_duration,
-            media_rate,
-            media_time,
-        })
-    }
+/// Entry of a version 1 `elst`
+#[derive(Clone, AttrRead, AttrWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EditV1 {
+    pub track_duration: u64,
+    pub media_time: u64,
+    pub media_rate: u32,
 }
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "leaf")]
 #[mp4(tag = "elst")]
+#[mp4(versions = "0,1")]
 pub struct Elst {
     #[mp4(header)]
     pub header: BoxHeader,
-    pub version: u8,
-    pub flags: [u8; 3],
+    #[mp4(fullbox)]
+    pub fullbox: FullBoxHeader,
     pub number_of_entries: u32,
-    pub edit_list: Vec<Edit>,
+    #[mp4(if_version = 1)]
+    #[mp4(count = "number_of_entries")]
+    pub edit_list: Either<Vec<EditV1>, Vec<Edit>>,
 }
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "internal")]
 #[mp4(tag = "edts")]
 pub struct Edts {
     #[mp4(header)]
     pub header: BoxHeader,
     pub edit_list: Elst,
+    #[mp4(unknown)]
+    pub others: Vec<RawBox>,
+    #[mp4(order)]
+    pub order: Vec<usize>,
+    #[mp4(trailer)]
+    pub trailer: Bytes,
 }
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "leaf")]
 #[mp4(tag = "mdhd")]
+#[mp4(versions = "0,1")]
 pub struct Mdhd {
     #[mp4(header)]
     pub header: BoxHeader,
-    pub version: u8,
-    pub flags: [u8; 3],
-    pub creation_time: u32,
-    pub modification_time: u32,
+    #[mp4(fullbox)]
+    pub fullbox: FullBoxHeader,
+    #[mp4(if_version = 1)]
+    pub creation_time: Either<u64, u32>,
+    #[mp4(if_version = 1)]
+    pub modification_time: Either<u64, u32>,
+    #[mp4(assert = "time_scale.0 != 0")]
     pub time_scale: Timescale,
-    pub duration: u32,
+    #[mp4(if_version = 1)]
+    pub duration: Either<u64, u32>,
     pub language: u16,
     pub quality: u16,
+    #[mp4(trailer)]
+    pub trailer: Bytes,
 }
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "internal")]
 #[mp4(tag = "minf")]
 pub struct Minf {
@@ -197,9 +250,15 @@ pub struct Minf {
     pub header: BoxHeader,
     pub dinf: Dinf,
     pub stbl: Stbl,
+    #[mp4(unknown)]
+    pub others: Vec<RawBox>,
+    #[mp4(order)]
+    pub order: Vec<usize>,
+    #[mp4(trailer)]
+    pub trailer: Bytes,
 }
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "internal")]
 #[mp4(tag = "mdia")]
 pub struct Mdia {
@@ -208,9 +267,15 @@ pub struct Mdia {
     pub mdhd: Mdhd,
     pub hdlr: Hdlr,
     pub minf: Minf,
+    #[mp4(unknown)]
+    pub others: Vec<RawBox>,
+    #[mp4(order)]
+    pub order: Vec<usize>,
+    #[mp4(trailer)]
+    pub trailer: Bytes,
 }
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "internal")]
 #[mp4(tag = "trak")]
 pub struct Trak {
@@ -219,78 +284,185 @@ pub struct Trak {
     pub tkhd: Tkhd,
     pub edts: Option<Edts>,
     pub mdia: Mdia,
+    #[mp4(unknown)]
+    pub others: Vec<RawBox>,
+    #[mp4(order)]
+    pub order: Vec<usize>,
+    #[mp4(trailer)]
+    pub trailer: Bytes,
 }
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "leaf")]
 #[mp4(tag = "hdlr")]
 pub struct Hdlr {
     #[mp4(header)]
     pub header: BoxHeader,
-    pub version: u8,
-    pub flags: [u8; 3],
+    #[mp4(fullbox)]
+    pub fullbox: FullBoxHeader,
     pub component_type: U32Tag,
     pub component_subtype: U32Tag,
     pub component_flags: [u8; 4],
     pub component_flags_mask: [u8; 4],
-    pub component_name: RawString,
+    /// Omitted entirely by some writers
+    pub component_name: Option<RawString>,
 }
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "internal")]
 #[mp4(tag = "udta")]
 pub struct Udta {
     #[mp4(header)]
     pub header: BoxHeader,
+    #[mp4(unknown)]
+    pub others: Vec<RawBox>,
+    #[mp4(order)]
+    pub order: Vec<usize>,
+    #[mp4(trailer)]
+    pub trailer: Bytes,
 }
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "leaf")]
 #[mp4(tag = "url ")]
 pub struct DataReference {
     #[mp4(header)]
     pub header: BoxHeader,
-    pub version: u8,
-    pub flags: [u8; 3],
-    pub data: Vec<u8>,
+    #[mp4(fullbox)]
+    pub fullbox: FullBoxHeader,
+    pub data: Bytes,
 }
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "leaf")]
 #[mp4(tag = "dref")]
 pub struct Dref {
     #[mp4(header)]
     pub header: BoxHeader,
-    pub version: u8,
-    pub flags: [u8; 3],
+    #[mp4(fullbox)]
+    pub fullbox: FullBoxHeader,
     pub number_of_entries: u32,
+    #[mp4(count = "number_of_entries")]
     pub drefs: Vec<DataReference>,
 }
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "internal")]
 #[mp4(tag = "dinf")]
 pub struct Dinf {
     #[mp4(header)]
     pub header: BoxHeader,
     pub dref: Dref,
+    #[mp4(unknown)]
+    pub others: Vec<RawBox>,
+    #[mp4(order)]
+    pub order: Vec<usize>,
+    #[mp4(trailer)]
+    pub trailer: Bytes,
 }
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// H.264 decoder configuration (ISO/IEC 14496-15 `AVCDecoderConfigurationRecord`)
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "leaf")]
+#[mp4(tag = "avcC")]
+pub struct AvcC {
+    #[mp4(header)]
+    pub header: BoxHeader,
+    pub configuration_version: u8,
+    pub profile_indication: u8,
+    pub profile_compatibility: u8,
+    pub level_indication: u8,
+    #[mp4(bits = 6)]
+    _reserved: u8,
+    /// Size of NAL unit lengths in samples, minus one
+    #[mp4(bits = 2)]
+    pub length_size_minus_one: u8,
+    /// Parameter sets
+    pub data: Bytes,
+}
+
+/// MPEG-4 elementary stream descriptor
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[mp4(boxtype = "leaf")]
+#[mp4(tag = "esds")]
+pub struct Esds {
+    #[mp4(header)]
+    pub header: BoxHeader,
+    #[mp4(fullbox)]
+    pub fullbox: FullBoxHeader,
+    pub descriptors: Option<Bytes>,
+}
+
+/// Pixel aspect ratio
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[mp4(boxtype = "leaf")]
+#[mp4(tag = "pasp")]
+pub struct Pasp {
+    #[mp4(header)]
+    pub header: BoxHeader,
+    pub h_spacing: u32,
+    pub v_spacing: u32,
+    #[mp4(trailer)]
+    pub trailer: Bytes,
+}
+
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[mp4(boxtype = "leaf")]
+#[mp4(tag = "btrt")]
+pub struct Btrt {
+    #[mp4(header)]
+    pub header: BoxHeader,
+    pub buffer_size: u32,
+    pub max_bitrate: Option<u32>,
+    pub avg_bitrate: Option<u32>,
+    #[mp4(trailer)]
+    pub trailer: Bytes,
+}
+
+/// Fields added by version 1 of the QuickTime sound sample description
+#[derive(Clone, AttrRead, AttrWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SoundDescriptionV1 {
+    pub samples_per_packet: u32,
+    pub bytes_per_packet: u32,
+    pub bytes_per_frame: u32,
+    pub bytes_per_sample: u32,
+}
+
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[mp4(boxtype = "hybrid")]
 pub enum GeneralSampleDescription {
     #[mp4(tag = "mp4a")]
     Mp4a {
         #[mp4(header)]
         header: BoxHeader,
-        _reserved: [u8; 6],
+        #[mp4(skip = 6)]
         data_reference_index: u16,
+        version: u16,
+        revision_level: u16,
+        vendor: U32Tag,
+        number_of_channels: u16,
+        sample_size: u16,
+        compression_id: u16,
+        packet_size: u16,
+        /// 16.16 fixed point
+        sample_rate: u32,
+        #[mp4(cond = "*version == 1")]
+        sound_v1: Option<SoundDescriptionV1>,
+        #[mp4(children)]
+        esds: Option<Esds>,
+        btrt: Option<Btrt>,
+        #[mp4(unknown)]
+        others: Vec<RawBox>,
+        #[mp4(order)]
+        order: Vec<usize>,
+        #[mp4(trailer)]
+        trailer: Bytes,
     },
     #[mp4(tag = "avc1")]
     Avc1 {
         #[mp4(header)]
         header: BoxHeader,
-        _reserved: [u8; 6],
+        #[mp4(skip = 6)]
         data_reference_index: u16,
         version: u16,
         revision: u16,
@@ -303,17 +475,32 @@ pub enum GeneralSampleDescription {
         vertical_resolution: u32,
         data_size: u32,
         frame_per_samples: u16,
+        compressor_name: [u8; 32],
+        depth: u16,
+        color_table_id: u16,
+        #[mp4(children)]
+        avcc: AvcC,
+        pasp: Option<Pasp>,
+        btrt: Option<Btrt>,
+        #[mp4(unknown)]
+        others: Vec<RawBox>,
+        #[mp4(order)]
+        order: Vec<usize>,
+        #[mp4(trailer)]
+        trailer: Bytes,
     },
     #[mp4(tag = "sowt")]
     Sowt {
         #[mp4(header)]
         header: BoxHeader,
+        /// Sound sample description, kept as is
+        data: Bytes,
     },
     #[mp4(tag = "Hap1")]
     Hap1 {
         #[mp4(header)]
         header: BoxHeader,
-        _reserved: [u8; 6],
+        #[mp4(skip = 6)]
         data_reference_index: u16,
         version: u16,
         revision: u16,
@@ -326,142 +513,125 @@ pub enum GeneralSampleDescription {
         vertical_resolution: u32,
         data_size: u32,
         frame_per_samples: u16,
+        /// Compressor name, depth, color table id and extensions, kept as is
+        trailer: Bytes,
     },
 }
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "leaf")]
 #[mp4(tag = "stsd")]
 pub struct Stsd {
     #[mp4(header)]
     pub header: BoxHeader,
-    pub version: u8,
-    pub flags: [u8; 3],
+    #[mp4(fullbox)]
+    pub fullbox: FullBoxHeader,
     pub number_of_entries: u32,
+    #[mp4(count = "number_of_entries")]
     pub sample_description_table: Vec<GeneralSampleDescription>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, AttrRead, AttrWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TimeToSample {
     pub sample_count: u32,
     pub sample_duration: u32,
 }
 
-#[async_trait::async_trait]
-impl AttrRead for TimeToSample {
-    async fn read_attr<R: AsyncRead + AsyncSeek + Unpin + Send>(
-        reader: &mut Reader<R>,
-    ) -> Result<Self, io::Error> {
-        let sample_count = AttrRead::read_attr(reader).await?;
-        let sample_duration = AttrRead::read_attr(reader).await?;
-        Ok(Self {
-            sample_count,
-            sample_duration,
-        })
-    }
-}
-
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, AttrRead, AttrWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SampleToChunk {
     pub first_chunk: u32,
     pub samples_per_chunk: u32,
     pub sample_description_id: u32,
 }
 
-#[async_trait::async_trait]
-impl AttrRead for SampleToChunk {
-    async fn read_attr<R: AsyncRead + AsyncSeek + Unpin + Send>(
-        reader: &mut Reader<R>,
-    ) -> Result<Self, io::Error> {
-        let first_chunk = AttrRead::read_attr(reader).await?;
-        let samples_per_chunk = AttrRead::read_attr(reader).await?;
-        let sample_description_id = AttrRead::read_attr(reader).await?;
-        Ok(Self {
-            first_chunk,
-            samples_per_chunk,
-            sample_description_id,
-        })
-    }
-}
-
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "leaf")]
 #[mp4(tag = "stts")]
 pub struct Stts {
     #[mp4(header)]
     pub header: BoxHeader,
-    pub version: u8,
-    pub flags: [u8; 3],
+    #[mp4(fullbox)]
+    pub fullbox: FullBoxHeader,
     pub number_of_entries: u32,
+    #[mp4(count = "number_of_entries")]
     pub time_to_sample_table: Vec<TimeToSample>,
 }
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "leaf")]
 #[mp4(tag = "stsc")]
+#[mp4(validate = "Stsc::validate")]
 pub struct Stsc {
     #[mp4(header)]
     pub header: BoxHeader,
-    pub version: u8,
-    pub flags: [u8; 3],
+    #[mp4(fullbox)]
+    pub fullbox: FullBoxHeader,
     pub number_of_entries: u32,
+    #[mp4(count = "number_of_entries")]
     pub sample_to_chunk_table: Vec<SampleToChunk>,
 }
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "leaf")]
 #[mp4(tag = "stsz")]
 pub struct Stsz {
     #[mp4(header)]
     pub header: BoxHeader,
-    pub version: u8,
-    pub flags: [u8; 3],
+    #[mp4(fullbox)]
+    pub fullbox: FullBoxHeader,
     pub sample_size: u32,
     pub number_of_entries: u32,
-    pub sample_size_table: Vec<u32>,
+    /// Present only when samples differ in size, i.e. `sample_size` is 0
+    #[mp4(cond = "*sample_size == 0")]
+    #[mp4(count = "number_of_entries")]
+    pub sample_size_table: Option<Vec<u32>>,
 }
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "leaf")]
 #[mp4(tag = "stco")]
 pub struct Stco {
     #[mp4(header)]
     pub header: BoxHeader,
-    pub version: u8,
-    pub flags: [u8; 3],
+    #[mp4(fullbox)]
+    pub fullbox: FullBoxHeader,
     pub number_of_entries: u32,
+    #[mp4(count = "number_of_entries")]
     pub chunk_offset_table: Vec<u32>,
 }
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "leaf")]
 #[mp4(tag = "co64")]
 pub struct Co64 {
     #[mp4(header)]
     pub header: BoxHeader,
-    pub version: u8,
-    pub flags: [u8; 3],
+    #[mp4(fullbox)]
+    pub fullbox: FullBoxHeader,
     pub number_of_entries: u32,
+    #[mp4(count = "number_of_entries")]
     pub chunk_offset_table: Vec<u64>,
 }
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "leaf")]
 #[mp4(tag = "sgpd")]
 pub struct Sgpd {
     #[mp4(header)]
     pub header: BoxHeader,
+    pub data: Bytes,
 }
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "leaf")]
 #[mp4(tag = "sbgp")]
 pub struct Sbgp {
     #[mp4(header)]
     pub header: BoxHeader,
+    pub data: Bytes,
 }
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "internal")]
 #[mp4(tag = "stbl")]
 pub struct Stbl {
@@ -473,6 +643,12 @@ pub struct Stbl {
     pub stsz: Stsz,
     pub stco: Option<Stco>,
     pub co64: Option<Co64>,
+    #[mp4(unknown)]
+    pub others: Vec<RawBox>,
+    #[mp4(order)]
+    pub order: Vec<usize>,
+    #[mp4(trailer)]
+    pub trailer: Bytes,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -482,19 +658,29 @@ pub struct Sample {
     pub size: usize,
 }
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+impl Sample {
+    /// The payload of the sample in `file`, the whole file held in memory, or `None` if it ends before the sample does
+    pub fn data<'a>(&self, file: &'a [u8]) -> Option<&'a [u8]> {
+        file.get(self.offset..self.offset.checked_add(self.size)?)
+    }
+}
+
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "leaf")]
 #[mp4(tag = "smhd")]
 pub struct Smhd {
     #[mp4(header)]
     pub header: BoxHeader,
-    pub version: u8,
-    pub flags: [u8; 3],
+    #[mp4(fullbox)]
+    pub fullbox: FullBoxHeader,
     pub balance: u16,
+    #[mp4(magic = 0)]
     _reserved: u16,
+    #[mp4(trailer)]
+    pub trailer: Bytes,
 }
 
-#[derive(Clone, BoxRead, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, BoxRead, BoxWrite, Debug, PartialEq, Eq, Serialize, Deserialize)]
 #[mp4(boxtype = "internal")]
 #[mp4(tag = "moov")]
 pub struct Moov {
@@ -503,6 +689,12 @@ pub struct Moov {
     pub mvhd: Mvhd,
     pub traks: Vec<Trak>,
     pub udta: Udta,
+    #[mp4(unknown)]
+    pub others: Vec<RawBox>,
+    #[mp4(order)]
+    pub order: Vec<usize>,
+    #[mp4(trailer)]
+    pub trailer: Bytes,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -514,8 +706,7 @@ impl From<&Stco> for Co64 {
     fn from(stco: &Stco) -> Self {
         Self {
             header: stco.header,
-            version: stco.version,
-            flags: stco.flags,
+            fullbox: stco.fullbox,
             number_of_entries: stco.number_of_entries,
             chunk_offset_table: stco
                 .chunk_offset_table
@@ -530,6 +721,8 @@ impl From<&Stco> for Co64 {
 pub enum SamplesError {
     #[error("co64 or stco not found")]
     Co64OrStcoNotFound,
+    #[error("sample at {offset} of {size} bytes is out of the file")]
+    OutOfFile { offset: usize, size: usize },
 }
 
 impl Trak {
@@ -543,7 +736,7 @@ impl Trak {
         .ok_or(SamplesError::Co64OrStcoNotFound)?;
         let chunk_offset_table = co64.chunk_offset_table;
         let _ = &self.mdia.minf.stbl.stsd.sample_description_table;
-        let sample_size_table = &self.mdia.minf.stbl.stsz.sample_size_table;
+        let stsz = &self.mdia.minf.stbl.stsz;
         let time_to_sample_table = &self.mdia.minf.stbl.stts.time_to_sample_table;
         let mut samples = Vec::new();
         let sample_len = time_to_sample_table
@@ -564,7 +757,7 @@ impl Trak {
         for time_to_sample in time_to_sample_table {
             for _ in 0..(time_to_sample.sample_count as usize) {
                 samples[sample_idx].duration =
-                    timescale.decode_duration(time_to_sample.sample_duration);
+                    timescale.decode_duration(time_to_sample.sample_duration as u64);
                 sample_idx += 1;
             }
         }
@@ -583,20 +776,70 @@ impl Trak {
                         "sample: {} offset: {}, offset_in_chunk: {}",
                         sample_idx, chunk_offset, offset_in_chunk
                     );
+                    let size = stsz.sample_size(sample_idx).unwrap_or(0) as usize;
                     samples[sample_idx].offset = offset_in_chunk + *chunk_offset as usize;
-                    samples[sample_idx].size = sample_size_table[sample_idx] as usize;
-                    offset_in_chunk += sample_size_table[sample_idx] as usize;
+                    samples[sample_idx].size = size;
+                    offset_in_chunk += size;
                     sample_idx += 1;
                 }
             }
         }
         Ok(samples)
     }
+
+    /// Payloads of [`Trak::samples`], borrowed from `file`, the whole file held in memory
+    pub fn sample_data<'a>(&self, file: &'a [u8]) -> Result<Vec<&'a [u8]>, SamplesError> {
+        self.samples()?
+            .iter()
+            .map(|sample| {
+                sample.data(file).ok_or(SamplesError::OutOfFile {
+                    offset: sample.offset,
+                    size: sample.size,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Stsc {
+    /// Chunks are numbered from 1 and each entry starts after the previous one
+    fn validate(&self) -> Result<(), ErrorKind> {
+        let mut last_chunk = 0;
+        for entry in &self.sample_to_chunk_table {
+            if entry.first_chunk == 0 {
+                return Err(ErrorKind::Invalid {
+                    reason: "first_chunk is 0".to_owned(),
+                });
+            }
+            if entry.first_chunk <= last_chunk {
+                return Err(ErrorKind::Invalid {
+                    reason: format!(
+                        "first_chunk {} does not follow {}",
+                        entry.first_chunk, last_chunk
+                    ),
+                });
+            }
+            last_chunk = entry.first_chunk;
+        }
+        Ok(())
+    }
+}
+
+impl Stsz {
+    /// Size of the `idx`-th sample
+    pub fn sample_size(&self, idx: usize) -> Option<u32> {
+        match &self.sample_size_table {
+            Some(table) => table.get(idx).copied(),
+            None => Some(self.sample_size),
+        }
+    }
 }
 
 impl Moov {
     pub fn video_duration(&self) -> Duration {
-        self.mvhd.time_scale.decode_duration(self.mvhd.duration)
+        self.mvhd
+            .time_scale
+            .decode_duration(self.mvhd.duration.to_u64())
     }
 }
 
@@ -639,4 +882,351 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn test_mdhd_v1() {
+        let mut mdhd = vec![0, 0, 0, 44];
+        mdhd.extend_from_slice(b"mdhd");
+        mdhd.extend_from_slice(&[1, 0, 0, 0]);
+        mdhd.extend_from_slice(&1u64.to_be_bytes());
+        mdhd.extend_from_slice(&2u64.to_be_bytes());
+        mdhd.extend_from_slice(&600u32.to_be_bytes());
+        mdhd.extend_from_slice(&6000u64.to_be_bytes());
+        mdhd.extend_from_slice(&[0x55, 0xc4, 0, 0]);
+        let mdhd: Mdhd =
+            blocking::AttrRead::read_attr(&mut blocking::Reader::from_slice(&mdhd)).unwrap();
+        assert_eq!(mdhd.modification_time, Either::Left(2));
+        assert_eq!(
+            mdhd.time_scale.decode_duration(mdhd.duration.to_u64()),
+            Duration::from_secs(10)
+        );
+        assert_eq!(mdhd.language, 0x55c4);
+    }
+
+    /// Box of `tag` holding `body`
+    fn child(tag: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut child = ((8 + body.len()) as u32).to_be_bytes().to_vec();
+        child.extend_from_slice(tag);
+        child.extend_from_slice(body);
+        child
+    }
+
+    /// `avc1` sample entry with `pasp` and `avcC` children
+    fn avc1_entry() -> Vec<u8> {
+        let mut avc1 = vec![0, 0, 0, 86 + 13 + 16];
+        avc1.extend_from_slice(b"avc1");
+        avc1.extend_from_slice(&[0; 6]);
+        avc1.extend_from_slice(&1u16.to_be_bytes());
+        avc1.extend_from_slice(&[0; 16]);
+        avc1.extend_from_slice(&1920u16.to_be_bytes());
+        avc1.extend_from_slice(&1080u16.to_be_bytes());
+        avc1.extend_from_slice(&[0; 14]);
+        avc1.extend_from_slice(&[0; 32]);
+        avc1.extend_from_slice(&[0, 24, 0xff, 0xff]);
+        avc1.extend_from_slice(&[0, 0, 0, 16]);
+        avc1.extend_from_slice(b"pasp");
+        avc1.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 1]);
+        avc1.extend_from_slice(&[0, 0, 0, 13]);
+        avc1.extend_from_slice(b"avcC");
+        avc1.extend_from_slice(&[1, 0x64, 0, 0x28, 0xff]);
+        avc1
+    }
+
+    #[tokio::test]
+    async fn test_stsd_round_trip() {
+        // video, audio and Hap entries, the video one with a child no field takes
+        let mut avcc = vec![1, 0x64, 0, 0x1f, 0xff, 0xe1, 0, 25];
+        avcc.extend_from_slice(&[
+            0x67, 0x64, 0, 0x1f, 0xac, 0xd9, 0x40, 0x50, 0x05, 0xbb, 0x01, 0x10, 0, 0, 0x03, 0,
+            0x10, 0, 0, 0x03, 0x03, 0xc0, 0xf1, 0x83, 0x19,
+        ]);
+        avcc.extend_from_slice(&[1, 0, 4, 0x68, 0xeb, 0xe3, 0xcb]);
+        let mut avc1 = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        avc1.extend_from_slice(&[0; 16]);
+        avc1.extend_from_slice(&[0x05, 0, 0x02, 0xd0, 0, 0x48, 0, 0, 0, 0x48, 0, 0]);
+        avc1.extend_from_slice(&[0, 0, 0, 0, 0, 1]);
+        avc1.extend_from_slice(&[0; 32]);
+        avc1.extend_from_slice(&[0, 0x18, 0xff, 0xff]);
+        avc1.extend(child(b"avcC", &avcc));
+        avc1.extend(child(b"colr", b"nclx\0\x01\0\x01\0\x01\0"));
+        avc1.extend(child(b"pasp", &[0, 0, 0, 1, 0, 0, 0, 1]));
+        avc1.extend(child(
+            b"btrt",
+            &[0, 0, 0, 0, 0, 0x1e, 0x84, 0x80, 0, 0x1e, 0x84, 0x80],
+        ));
+        let mut sowt = vec![0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0];
+        sowt.extend_from_slice(&[0, 2, 0, 16, 0, 0, 0, 0, 0xac, 0x44, 0, 0]);
+        let mut hap1 = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        hap1.extend_from_slice(&[0; 16]);
+        hap1.extend_from_slice(&[0x07, 0x80, 0x04, 0x38, 0, 0x48, 0, 0, 0, 0x48, 0, 0]);
+        hap1.extend_from_slice(&[0, 0, 0, 0, 0, 1]);
+        hap1.extend_from_slice(&[3, b'H', b'a', b'p']);
+        hap1.extend_from_slice(&[0; 28]);
+        hap1.extend_from_slice(&[0, 0x18, 0xff, 0xff]);
+        let mut body = vec![0, 0, 0, 0, 0, 0, 0, 3];
+        body.extend(child(b"avc1", &avc1));
+        body.extend(child(b"sowt", &sowt));
+        body.extend(child(b"Hap1", &hap1));
+        let stsd = child(b"stsd", &body);
+
+        let parsed: Stsd =
+            blocking::AttrRead::read_attr(&mut blocking::Reader::from_slice(&stsd)).unwrap();
+        let GeneralSampleDescription::Avc1 { avcc, others, .. } =
+            &parsed.sample_description_table[0]
+        else {
+            panic!("unexpected {:?}", parsed.sample_description_table[0]);
+        };
+        assert_eq!(avcc.length_size_minus_one, 3);
+        assert_eq!(&others[0].header.id, b"colr");
+        let mut written = Vec::new();
+        movparse_box::AttrWrite::write_attr(&parsed, &mut written)
+            .await
+            .unwrap();
+        assert_eq!(written, stsd);
+    }
+
+    #[tokio::test]
+    async fn test_moov_round_trip() {
+        // boxes in the order a muxer writes them, with children no field takes
+        let mut mvhd = vec![0; 12];
+        mvhd.extend_from_slice(&1000u32.to_be_bytes());
+        mvhd.extend_from_slice(&5000u32.to_be_bytes());
+        mvhd.extend_from_slice(&[0, 1, 0, 0, 1, 0]);
+        mvhd.extend_from_slice(&[0; 10 + 36 + 24]);
+        mvhd.extend_from_slice(&2u32.to_be_bytes());
+        let mut tkhd = vec![0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+        tkhd.extend_from_slice(&[0; 4]);
+        tkhd.extend_from_slice(&5000u32.to_be_bytes());
+        tkhd.extend_from_slice(&[0; 8 + 8 + 36]);
+        tkhd.extend_from_slice(&[0x07, 0x80, 0, 0, 0x04, 0x38, 0, 0]);
+        let elst = child(
+            b"elst",
+            &[
+                0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0x13, 0x88, 0, 0, 0, 0, 0, 1, 0, 0,
+            ],
+        );
+        let mut mdhd = vec![0; 12];
+        mdhd.extend_from_slice(&12800u32.to_be_bytes());
+        mdhd.extend_from_slice(&64000u32.to_be_bytes());
+        mdhd.extend_from_slice(&[0x55, 0xc4, 0, 0]);
+        let mut hdlr = vec![0; 8];
+        hdlr.extend_from_slice(b"vide");
+        hdlr.extend_from_slice(&[0; 12]);
+        hdlr.extend_from_slice(b"VideoHandler\0");
+        let dref = child(
+            b"dref",
+            &[vec![0, 0, 0, 0, 0, 0, 0, 1], child(b"url ", &[0, 0, 0, 1])].concat(),
+        );
+        let mut stsd = vec![0, 0, 0, 0, 0, 0, 0, 1];
+        stsd.extend(avc1_entry());
+        let stbl = [
+            child(b"stsd", &stsd),
+            child(
+                b"stts",
+                &[0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0x02, 0],
+            ),
+            child(b"stss", &[0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1]),
+            child(
+                b"stsc",
+                &[0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1],
+            ),
+            child(
+                b"stsz",
+                &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0x03, 0xe8],
+            ),
+            child(b"stco", &[0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0x30]),
+        ]
+        .concat();
+        let minf = [
+            child(b"vmhd", &[0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0]),
+            child(b"dinf", &dref),
+            child(b"stbl", &stbl),
+        ]
+        .concat();
+        let mdia = [
+            child(b"mdhd", &mdhd),
+            child(b"hdlr", &hdlr),
+            child(b"minf", &minf),
+        ]
+        .concat();
+        let trak = [
+            child(b"tkhd", &tkhd),
+            child(b"edts", &elst),
+            child(b"mdia", &mdia),
+        ]
+        .concat();
+        // QuickTime terminates the user data list with a 32-bit zero
+        let mut udta = child(b"\xa9too", b"Lavf58.76.100");
+        udta.extend_from_slice(&[0; 4]);
+        let moov = child(
+            b"moov",
+            &[
+                child(b"mvhd", &mvhd),
+                child(b"trak", &trak),
+                child(b"udta", &udta),
+            ]
+            .concat(),
+        );
+
+        let parsed: Moov =
+            blocking::AttrRead::read_attr(&mut blocking::Reader::from_slice(&moov)).unwrap();
+        let minf = &parsed.traks[0].mdia.minf;
+        assert_eq!(minf.order, [2, 0, 1]);
+        assert_eq!(&minf.others[0].header.id, b"vmhd");
+        assert_eq!(minf.stbl.order, [0, 1, 6, 2, 3, 4]);
+        assert_eq!(&minf.stbl.others[0].header.id, b"stss");
+        assert_eq!(parsed.udta.trailer.as_ref(), [0; 4]);
+        let samples = parsed.traks[0].samples().unwrap();
+        assert_eq!((samples[0].offset, samples[0].size), (48, 1000));
+        let file = vec![0; 1048];
+        let data = parsed.traks[0].sample_data(&file).unwrap();
+        assert_eq!(data[0].as_ptr(), file[48..].as_ptr());
+        assert_eq!(data[0].len(), 1000);
+        assert!(matches!(
+            parsed.traks[0].sample_data(&file[..1047]),
+            Err(SamplesError::OutOfFile {
+                offset: 48,
+                size: 1000
+            })
+        ));
+
+        let mut written = Vec::new();
+        movparse_box::AttrWrite::write_attr(&parsed, &mut written)
+            .await
+            .unwrap();
+        assert_eq!(written, moov);
+    }
+
+    #[test]
+    fn test_avc1_children() {
+        let avc1 = avc1_entry();
+        let entry: GeneralSampleDescription =
+            blocking::AttrRead::read_attr(&mut blocking::Reader::from_slice(&avc1)).unwrap();
+        let GeneralSampleDescription::Avc1 {
+            width,
+            height,
+            avcc,
+            pasp,
+            btrt,
+            ..
+        } = entry
+        else {
+            panic!("unexpected {:?}", entry);
+        };
+        assert_eq!((width, height), (1920, 1080));
+        assert_eq!(
+            (
+                avcc.profile_indication,
+                avcc.level_indication,
+                avcc.length_size_minus_one
+            ),
+            (0x64, 0x28, 3)
+        );
+        assert_eq!(pasp.map(|pasp| pasp.h_spacing), Some(1));
+        assert!(btrt.is_none());
+    }
+
+    /// Fields of the closed `box` spans, in the order they closed
+    #[cfg(feature = "tracing")]
+    #[derive(Clone, Default)]
+    struct BoxSpans(
+        std::sync::Arc<std::sync::Mutex<Vec<std::collections::BTreeMap<&'static str, String>>>>,
+    );
+
+    #[cfg(feature = "tracing")]
+    impl<S> tracing_subscriber::Layer<S> for BoxSpans
+    where
+        S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        fn on_new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+            id: &tracing::span::Id,
+            ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let mut fields = Fields::default();
+            attrs.record(&mut fields);
+            ctx.span(id).unwrap().extensions_mut().insert(fields);
+        }
+
+        fn on_record(
+            &self,
+            id: &tracing::span::Id,
+            values: &tracing::span::Record<'_>,
+            ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let span = ctx.span(id).unwrap();
+            let mut extensions = span.extensions_mut();
+            values.record(extensions.get_mut::<Fields>().unwrap());
+        }
+
+        fn on_close(&self, id: tracing::span::Id, ctx: tracing_subscriber::layer::Context<'_, S>) {
+            let span = ctx.span(&id).unwrap();
+            if span.name() == "box" {
+                let fields = span.extensions_mut().remove::<Fields>().unwrap();
+                self.0.lock().unwrap().push(fields.0);
+            }
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[derive(Default)]
+    struct Fields(std::collections::BTreeMap<&'static str, String>);
+
+    #[cfg(feature = "tracing")]
+    impl tracing::field::Visit for Fields {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0.insert(field.name(), format!("{:?}", value));
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_box_spans() {
+        let spans = BoxSpans::default();
+        let avc1 = avc1_entry();
+        tracing::subscriber::with_default(
+            tracing_subscriber::registry().with(spans.clone()),
+            || {
+                let _: GeneralSampleDescription =
+                    blocking::AttrRead::read_attr(&mut blocking::Reader::from_slice(&avc1))
+                        .unwrap();
+            },
+        );
+        let spans = spans.0.lock().unwrap();
+        let fields = |key: &str| {
+            spans
+                .iter()
+                .map(|span| span[key].as_str())
+                .collect::<Vec<_>>()
+        };
+        // children close before the sample entry holding them
+        assert_eq!(fields("fourcc"), ["pasp", "avcC", "avc1"]);
+        assert_eq!(fields("offset"), ["86", "102", "0"]);
+        assert_eq!(fields("size"), ["16", "13", "115"]);
+        assert_eq!(fields("bytes_read"), ["8", "5", "107"]);
+        assert!(spans[1]["path"].contains("avcC"));
+        assert!(spans.iter().all(|span| span.contains_key("elapsed_us")));
+    }
+
+    #[test]
+    fn test_stsc_chunks() {
+        let mut stsc = vec![0, 0, 0, 16 + 24];
+        stsc.extend_from_slice(b"stsc");
+        stsc.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 2]);
+        for first_chunk in [1u32, 3] {
+            stsc.extend_from_slice(&first_chunk.to_be_bytes());
+            stsc.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 1]);
+        }
+        let parsed: Stsc =
+            blocking::AttrRead::read_attr(&mut blocking::Reader::from_slice(&stsc)).unwrap();
+        assert_eq!(parsed.sample_to_chunk_table[1].first_chunk, 3);
+
+        // an entry starting at chunk 0 would underflow the chunk index in `Trak::samples`
+        stsc[19] = 0;
+        let err = <Stsc as blocking::AttrRead>::read_attr(&mut blocking::Reader::from_slice(&stsc))
+            .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Invalid { .. }));
+        assert_eq!(err.path.to_string(), "stsc");
+    }
 }